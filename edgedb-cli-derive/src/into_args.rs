@@ -25,15 +25,18 @@ pub fn structure(s: &types::Struct) -> TokenStream {
                 .map(|s| s.value().to_string())
                 .unwrap_or_else(
                     || s.attrs.rename_all.convert(&field.ident.to_string()));
-            if field.multiple {
-                abort!(field.ident, "multiple is not implemented");
-            }
             match field.parse.kind {
                 FromOccurrences => {
                     abort!(field.ident, "occurrendes are not implemented");
                 }
                 FromStr | FromOsStr | TryFromStr | TryFromOsStr => {
-                    if field.optional {
+                    if field.multiple {
+                        args.push(quote! {
+                            for value in &self.#ident {
+                                process.arg(#long).args([value]);
+                            }
+                        });
+                    } else if field.optional {
                         args.push(quote! {
                             if let Some(value) = &self.#ident {
                                 process.arg(#long).args([value]);
@@ -413,6 +413,7 @@ fn try_project_init(new_layout: bool) -> anyhow::Result<InitResult> {
         let options = crate::options::CloudOptions {
             cloud_base_url: None,
             cloud_access_token: None,
+            cloud_profile: None,
         };
         let dir = fs::canonicalize(&dir)
             .with_context(|| format!("failed to canonicalize dir {:?}", dir))?;
@@ -7,6 +7,7 @@ use crate::cloud::options;
 use crate::options::CloudOptions;
 use crate::portable::local::write_json;
 use crate::print;
+use crate::question;
 
 const AUTHENTICATION_WAIT_TIME: Duration = Duration::from_secs(10 * 60);
 const AUTHENTICATION_POLL_INTERVAL: Duration = Duration::from_secs(1);
@@ -47,7 +48,7 @@ pub async fn do_login(client: &CloudClient) -> anyhow::Result<()> {
                 token: Some(token),
             }) => {
                 write_json(
-                    &cloud_config_file()?,
+                    &cloud_config_file(client.profile.as_deref())?,
                     "cloud config",
                     &CloudConfig {
                         access_token: Some(token),
@@ -70,9 +71,26 @@ pub async fn do_login(client: &CloudClient) -> anyhow::Result<()> {
     )
 }
 
-pub async fn logout(_c: &options::Logout) -> anyhow::Result<()> {
+/// Returns an authenticated client, offering to log in first if there are
+/// no credentials and we're running in a terminal
+pub async fn authenticated_client(options: &CloudOptions) -> anyhow::Result<CloudClient> {
+    let client = CloudClient::new(options)?;
+    if !client.is_logged_in && atty::is(atty::Stream::Stdout) {
+        let mut q = question::Confirm::new("Not logged in. Log in now?");
+        if q.default(true).ask()? {
+            do_login(&client).await?;
+            let client = CloudClient::new(options)?;
+            client.ensure_authenticated(false)?;
+            return Ok(client);
+        }
+    }
+    client.ensure_authenticated(false)?;
+    Ok(client)
+}
+
+pub async fn logout(_c: &options::Logout, options: &CloudOptions) -> anyhow::Result<()> {
     write_json(
-        &cloud_config_file()?,
+        &cloud_config_file(options.cloud_profile.as_deref())?,
         "cloud config",
         &CloudConfig { access_token: None },
     )?;
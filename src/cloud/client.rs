@@ -1,20 +1,27 @@
+use std::collections::HashSet;
 use std::convert::TryInto;
 use std::env;
 use std::fs;
 use std::io;
 use std::path::PathBuf;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 
+use async_std::task;
+use surf::http::Method;
 use surf::http::auth::{AuthenticationScheme, Authorization};
 
 use crate::commands::ExitCode;
 use crate::options::CloudOptions;
 use crate::platform::config_dir;
+use crate::portable::local::is_valid_name;
+use crate::portable::repository::parse_retry_after;
 use crate::print;
 
 const EDGEDB_CLOUD_BASE_URL: &str = "https://free-tier0.ovh-us-west-2.edgedb.cloud";
 const EDGEDB_CLOUD_API_VERSION: &str = "/v1/";
 const EDGEDB_CLOUD_API_TIMEOUT: u64 = 10;
+const MAX_ATTEMPTS: u32 = 5;
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
 
 #[derive(Debug, serde::Deserialize)]
 struct ErrorResponse {
@@ -22,10 +29,36 @@ struct ErrorResponse {
     error: Option<String>,
 }
 
+/// Collection response, either a plain list or a page with a link to the
+/// next one
+#[derive(Debug, serde::Deserialize)]
+#[serde(untagged)]
+enum Page<T> {
+    List(Vec<T>),
+    Paginated {
+        items: Vec<T>,
+        #[serde(default)]
+        next: Option<String>,
+    },
+}
+
 #[derive(Debug, thiserror::Error)]
 #[error("HTTP error: {0}")]
 pub struct HttpError(surf::Error);
 
+#[derive(Debug, thiserror::Error)]
+#[error("not authenticated to EdgeDB Cloud, run `edgedb cloud login` first")]
+pub struct NotAuthenticated;
+
+#[derive(Debug, thiserror::Error)]
+#[error("EdgeDB Cloud API error: {status}{}",
+        .error.as_ref().map(|e| format!(": {}", e)).unwrap_or_default())]
+pub struct ApiError {
+    pub code: surf::StatusCode,
+    status: String,
+    error: Option<String>,
+}
+
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub struct CloudConfig {
     pub access_token: Option<String>,
@@ -35,6 +68,7 @@ pub struct CloudClient {
     client: surf::Client,
     pub is_logged_in: bool,
     pub base_url: String,
+    pub profile: Option<String>,
 }
 
 impl CloudClient {
@@ -42,7 +76,8 @@ impl CloudClient {
         let access_token = if let Some(access_token) = &options.cloud_access_token {
             Some(access_token.into())
         } else {
-            match fs::read_to_string(cloud_config_file()?) {
+            let config_file = cloud_config_file(options.cloud_profile.as_deref())?;
+            match fs::read_to_string(config_file) {
                 Ok(data) if data.is_empty() => None,
                 Ok(data) => {
                     let config: CloudConfig = serde_json::from_str(&data)?;
@@ -79,6 +114,7 @@ impl CloudClient {
             client: config.try_into()?,
             is_logged_in,
             base_url,
+            profile: options.cloud_profile.clone(),
         })
     }
 
@@ -93,24 +129,70 @@ impl CloudClient {
         }
     }
 
-    pub async fn request<T: serde::de::DeserializeOwned>(
+    async fn request<T: serde::de::DeserializeOwned>(
         &self,
-        req: surf::RequestBuilder,
+        method: Method,
+        uri: &str,
+        body: Option<surf::Body>,
+    ) -> anyhow::Result<T> {
+        let resp = self.send(method, uri, body).await?;
+        Self::read_response(resp).await
+    }
+
+    /// Sends the request retrying failures that are safe to retry (see
+    /// `is_retryable`) with exponential backoff, honoring `Retry-After`
+    async fn send(
+        &self,
+        method: Method,
+        uri: &str,
+        body: Option<surf::Body>,
+    ) -> anyhow::Result<surf::Response> {
+        // body is buffered so that it can be sent again on retry
+        let body = match body {
+            Some(body) => {
+                let mime = body.mime().clone();
+                Some((mime, body.into_bytes().await.map_err(HttpError)?))
+            }
+            None => None,
+        };
+        let mut attempt = 1;
+        loop {
+            let mut req = self.client.request(method, uri);
+            if let Some((mime, bytes)) = &body {
+                let mut body = surf::Body::from_bytes(bytes.clone());
+                body.set_mime(mime.clone());
+                req = req.body(body);
+            }
+            let resp = req.await.map_err(HttpError)?;
+            let status = resp.status();
+            if !is_retryable(method, status) || attempt >= MAX_ATTEMPTS {
+                return Ok(resp);
+            }
+            let delay = resp.header("Retry-After")
+                .and_then(|val| parse_retry_after(val.last().as_str(),
+                                                  SystemTime::now()))
+                .unwrap_or_else(|| Duration::from_secs(1 << (attempt - 1)))
+                .min(MAX_RETRY_DELAY);
+            log::warn!("EdgeDB Cloud API error: {}. \
+                        Will retry in {} seconds.", status, delay.as_secs());
+            task::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    async fn read_response<T: serde::de::DeserializeOwned>(
+        mut resp: surf::Response,
     ) -> anyhow::Result<T> {
-        let mut resp = req.await.map_err(HttpError)?;
+        if resp.status() == surf::StatusCode::Unauthorized {
+            return Err(NotAuthenticated.into());
+        }
         if !resp.status().is_success() {
             let ErrorResponse { status, error } = resp.body_json().await.map_err(HttpError)?;
-            if let Some(error) = error {
-                anyhow::bail!(format!(
-                    "Failed to create authentication session: {}: {}",
-                    status, error
-                ));
-            } else {
-                anyhow::bail!(format!(
-                    "Failed to create authentication session: {}",
-                    status
-                ));
-            }
+            return Err(ApiError {
+                code: resp.status(),
+                status,
+                error,
+            }.into());
         }
         Ok(resp.body_json().await.map_err(HttpError)?)
     }
@@ -119,7 +201,39 @@ impl CloudClient {
         &self,
         uri: impl AsRef<str>,
     ) -> anyhow::Result<T> {
-        self.request(self.client.get(uri)).await
+        self.request(Method::Get, uri.as_ref(), None).await
+    }
+
+    /// Fetches all items of a collection, following pagination either by
+    /// `Link: <...>; rel="next"` header or by `next` field of the response
+    pub async fn get_all<T: serde::de::DeserializeOwned>(
+        &self,
+        uri: impl AsRef<str>,
+    ) -> anyhow::Result<Vec<T>> {
+        let mut result = Vec::new();
+        let mut uri = uri.as_ref().to_string();
+        let mut seen = HashSet::new();
+        loop {
+            let resp = self.send(Method::Get, &uri, None).await?;
+            let link = resp.header("Link")
+                .and_then(|val| next_link(val.last().as_str()));
+            let next = match Self::read_response(resp).await? {
+                Page::List(items) => {
+                    result.extend(items);
+                    link
+                }
+                Page::Paginated { items, next } => {
+                    result.extend(items);
+                    next.or(link)
+                }
+            };
+            seen.insert(uri);
+            match next {
+                // guard against a server returning pages in a cycle
+                Some(next) if !seen.contains(&next) => uri = next,
+                _ => return Ok(result),
+            }
+        }
     }
 
     pub async fn post<T: serde::de::DeserializeOwned>(
@@ -127,17 +241,123 @@ impl CloudClient {
         uri: impl AsRef<str>,
         body: impl Into<surf::Body>,
     ) -> anyhow::Result<T> {
-        self.request(self.client.post(uri).body(body)).await
+        self.request(Method::Post, uri.as_ref(), Some(body.into())).await
+    }
+
+    pub async fn put<T: serde::de::DeserializeOwned>(
+        &self,
+        uri: impl AsRef<str>,
+        body: impl Into<surf::Body>,
+    ) -> anyhow::Result<T> {
+        self.request(Method::Put, uri.as_ref(), Some(body.into())).await
     }
 
     pub async fn delete<T: serde::de::DeserializeOwned>(
         &self,
         uri: impl AsRef<str>,
     ) -> anyhow::Result<T> {
-        self.request(self.client.delete(uri)).await
+        self.request(Method::Delete, uri.as_ref(), None).await
     }
 }
 
-pub fn cloud_config_file() -> anyhow::Result<PathBuf> {
-    Ok(config_dir()?.join("cloud.json"))
+/// Extracts URL of the next page from the `Link` header
+fn next_link(value: &str) -> Option<String> {
+    value.split(',').find_map(|link| {
+        let mut parts = link.split(';');
+        let url = parts.next()?.trim()
+            .strip_prefix('<')?.strip_suffix('>')?;
+        let is_next = parts.any(|param| {
+            let param = param.trim();
+            param == "rel=next" || param == "rel=\"next\""
+        });
+        if is_next { Some(url.to_string()) } else { None }
+    })
+}
+
+pub fn cloud_config_file(profile: Option<&str>) -> anyhow::Result<PathBuf> {
+    if let Some(profile) = profile {
+        if !is_valid_name(profile) {
+            anyhow::bail!("invalid profile name {:?}", profile);
+        }
+        Ok(cloud_profiles_dir()?.join(format!("{}.json", profile)))
+    } else {
+        Ok(config_dir()?.join("cloud.json"))
+    }
+}
+
+fn cloud_profiles_dir() -> anyhow::Result<PathBuf> {
+    Ok(config_dir()?.join("cloud-profiles"))
+}
+
+/// Whether the request can be sent again after receiving `status`
+///
+/// Server errors are only retried for idempotent methods, because POST might
+/// have been already processed (e.g. instance created). `429 Too Many
+/// Requests` means that the request was not processed at all.
+fn is_retryable(method: Method, status: surf::StatusCode) -> bool {
+    let idempotent = matches!(method,
+        Method::Get | Method::Put | Method::Delete);
+    status == surf::StatusCode::TooManyRequests
+        || (idempotent && status.is_server_error())
+}
+
+/// Returns all profiles that have a config file, `None` is the default one
+pub fn all_profiles() -> anyhow::Result<Vec<Option<String>>> {
+    let mut result = Vec::new();
+    if cloud_config_file(None)?.exists() {
+        result.push(None);
+    }
+    let dir = cloud_profiles_dir()?;
+    let dir_entries = match fs::read_dir(&dir) {
+        Ok(d) => d,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(result),
+        Err(e) => return Err(e)?,
+    };
+    let mut names = Vec::new();
+    for item in dir_entries {
+        let item = item?;
+        if let Ok(filename) = item.file_name().into_string() {
+            match filename.strip_suffix(".json") {
+                Some(name) if is_valid_name(name) => {
+                    names.push(name.to_string());
+                }
+                _ => log::debug!("Skipping unknown file {:?}", item.path()),
+            }
+        }
+    }
+    names.sort();
+    result.extend(names.into_iter().map(Some));
+    Ok(result)
+}
+
+#[cfg(test)]
+mod test {
+    use surf::http::Method;
+    use surf::StatusCode;
+    use test_case::test_case;
+
+    use super::{is_retryable, next_link};
+
+    #[test_case(r#"<https://api/v1/instances/?page=2>; rel="next""#
+                => Some("https://api/v1/instances/?page=2".into()))]
+    #[test_case(r#"<https://api/?page=1>; rel="prev", <https://api/?page=3>; rel="next""#
+                => Some("https://api/?page=3".into()))]
+    #[test_case("</instances/?cursor=abc>; rel=next"
+                => Some("/instances/?cursor=abc".into()))]
+    #[test_case(r#"<https://api/?page=9>; rel="last""# => None)]
+    #[test_case("" => None)]
+    fn link(value: &str) -> Option<String> {
+        next_link(value)
+    }
+
+    #[test_case(Method::Get, StatusCode::BadGateway => true)]
+    #[test_case(Method::Put, StatusCode::ServiceUnavailable => true)]
+    #[test_case(Method::Delete, StatusCode::InternalServerError => true)]
+    #[test_case(Method::Post, StatusCode::BadGateway => false)]
+    #[test_case(Method::Post, StatusCode::TooManyRequests => true)]
+    #[test_case(Method::Get, StatusCode::TooManyRequests => true)]
+    #[test_case(Method::Get, StatusCode::NotFound => false)]
+    fn retryable(method: Method, status: StatusCode) -> bool {
+        is_retryable(method, status)
+    }
 }
@@ -1,8 +1,13 @@
 use async_std::task;
 
 use crate::options::CloudOptions;
-use crate::cloud::options::CloudCommand;
+use crate::cloud::options::{CloudCommand, CloudInstanceCommand};
+use crate::cloud::options::{CloudOrgsCommand};
+use crate::cloud::options::{InstanceSecretCommand, InstanceMaintenanceCommand};
 use crate::cloud::auth;
+use crate::cloud::maintenance;
+use crate::cloud::ops;
+use crate::cloud::secrets;
 
 
 pub fn cloud_main(cmd: &CloudCommand, options: &CloudOptions) -> anyhow::Result<()> {
@@ -13,7 +18,74 @@ pub fn cloud_main(cmd: &CloudCommand, options: &CloudOptions) -> anyhow::Result<
             task::block_on(auth::login(c, options))
         }
         Logout(c) => {
-            task::block_on(auth::logout(c))
+            task::block_on(auth::logout(c, options))
         }
+        Instance(c) => instance_main(c, options),
+        Orgs(c) => orgs_main(c, options),
+    }
+}
+
+fn orgs_main(cmd: &CloudOrgsCommand, options: &CloudOptions)
+    -> anyhow::Result<()>
+{
+    use crate::cloud::options::OrgsCommand::*;
+
+    match &cmd.subcommand {
+        List(c) => task::block_on(ops::list_orgs(c, options)),
+    }
+}
+
+fn instance_main(cmd: &CloudInstanceCommand, options: &CloudOptions)
+    -> anyhow::Result<()>
+{
+    use crate::cloud::options::InstanceCommand::*;
+
+    match &cmd.subcommand {
+        Status(c) => {
+            task::block_on(ops::status(c, options))
+        }
+        Resize(c) => {
+            task::block_on(ops::resize(c, options))
+        }
+        Export(c) => {
+            task::block_on(ops::export(c, options))
+        }
+        Refresh(c) => {
+            task::block_on(ops::refresh(c, options))
+        }
+        Metrics(c) => {
+            task::block_on(ops::metrics(c, options))
+        }
+        Logs(c) => {
+            task::block_on(ops::logs(c, options))
+        }
+        Secret(c) => secret_main(c, options),
+        Versions(c) => {
+            task::block_on(ops::versions(c, options))
+        }
+        MaintenanceWindow(c) => maintenance_main(c, options),
+    }
+}
+
+fn maintenance_main(cmd: &InstanceMaintenanceCommand, options: &CloudOptions)
+    -> anyhow::Result<()>
+{
+    use crate::cloud::options::MaintenanceCommand::*;
+
+    match &cmd.subcommand {
+        Show(c) => task::block_on(maintenance::show(c, options)),
+        Set(c) => task::block_on(maintenance::set(c, options)),
+    }
+}
+
+fn secret_main(cmd: &InstanceSecretCommand, options: &CloudOptions)
+    -> anyhow::Result<()>
+{
+    use crate::cloud::options::SecretCommand::*;
+
+    match &cmd.subcommand {
+        Set(c) => task::block_on(secrets::set(c, options)),
+        List(c) => task::block_on(secrets::list(c, options)),
+        Unset(c) => task::block_on(secrets::unset(c, options)),
     }
 }
@@ -0,0 +1,138 @@
+use anyhow::Context;
+
+use crate::cloud::auth;
+use crate::cloud::client::CloudClient;
+use crate::cloud::ops::{get_cloud_instance_by_name, CloudInstance};
+use crate::cloud::options::{MaintenanceSet, MaintenanceShow};
+use crate::options::CloudOptions;
+use crate::print;
+use crate::table;
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct MaintenanceWindow {
+    pub day: String,
+    pub time: String,
+    pub timezone: String,
+}
+
+impl MaintenanceWindow {
+    pub fn display(&self) -> String {
+        format!("{} {} {}", self.day, self.time, self.timezone)
+    }
+}
+
+/// Validates and normalizes time in `HH:MM` format
+pub fn parse_time(value: &str) -> anyhow::Result<String> {
+    let (hours, minutes) = value
+        .split_once(':')
+        .context("time must be in HH:MM format")?;
+    let hours: u8 = hours.parse().context("invalid hours")?;
+    let minutes: u8 = minutes.parse().context("invalid minutes")?;
+    if hours > 23 || minutes > 59 || value.len() > 5 {
+        anyhow::bail!("time must be between 00:00 and 23:59");
+    }
+    Ok(format!("{:02}:{:02}", hours, minutes))
+}
+
+/// Validates timezone, either `UTC` or a fixed offset like `UTC+05:30`
+pub fn parse_timezone(value: &str) -> anyhow::Result<String> {
+    let offset = match value.to_ascii_uppercase().strip_prefix("UTC") {
+        Some("") => return Ok("UTC".into()),
+        Some(offset) => offset.to_string(),
+        None => anyhow::bail!("timezone must be `UTC` or `UTC+HH:MM`"),
+    };
+    let rest = match offset.strip_prefix(&['+', '-'][..]) {
+        Some(rest) => rest,
+        None => anyhow::bail!("timezone offset must start with `+` or `-`"),
+    };
+    let sign = &offset[..1];
+    let (hours, minutes) = rest.split_once(':').unwrap_or((rest, "0"));
+    let hours: u8 = hours.parse().context("invalid timezone offset")?;
+    let minutes: u8 = minutes.parse().context("invalid timezone offset")?;
+    if hours > 14 || minutes > 59 {
+        anyhow::bail!("timezone offset must be between -14:00 and +14:00");
+    }
+    Ok(format!("UTC{}{:02}:{:02}", sign, hours, minutes))
+}
+
+pub async fn get_window(
+    client: &CloudClient,
+    instance: &CloudInstance,
+) -> anyhow::Result<Option<MaintenanceWindow>> {
+    client
+        .get(format!("instances/{}/maintenance-window", instance.id))
+        .await
+}
+
+pub async fn show(cmd: &MaintenanceShow, options: &CloudOptions) -> anyhow::Result<()> {
+    let client = auth::authenticated_client(options).await?;
+    let instance = get_cloud_instance_by_name(&cmd.name, &client).await?;
+    let window = get_window(&client, &instance).await?;
+    if cmd.json {
+        println!("{}", serde_json::to_string_pretty(&window)?);
+    } else if let Some(window) = window {
+        table::settings(&[
+            ("Instance", &instance.name),
+            ("Day", &window.day),
+            ("Time", &window.time),
+            ("Timezone", &window.timezone),
+        ]);
+    } else {
+        print::warn(format!(
+            "No maintenance window is set for instance {}, \
+             maintenance may happen at any time.",
+            instance.name
+        ));
+    }
+    Ok(())
+}
+
+pub async fn set(cmd: &MaintenanceSet, options: &CloudOptions) -> anyhow::Result<()> {
+    let client = auth::authenticated_client(options).await?;
+    let instance = get_cloud_instance_by_name(&cmd.name, &client).await?;
+    let window: MaintenanceWindow = client
+        .put(
+            format!("instances/{}/maintenance-window", instance.id),
+            serde_json::to_value(MaintenanceWindow {
+                day: cmd.day.clone(),
+                time: cmd.time.clone(),
+                timezone: cmd.timezone.clone(),
+            })?,
+        )
+        .await?;
+    print::success(format!(
+        "Maintenance window of {} is set to {}.",
+        instance.name,
+        window.display()
+    ));
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use test_case::test_case;
+
+    use super::{parse_time, parse_timezone};
+
+    #[test_case("03:00" => Some("03:00".into()))]
+    #[test_case("3:5" => Some("03:05".into()))]
+    #[test_case("23:59" => Some("23:59".into()))]
+    #[test_case("24:00" => None)]
+    #[test_case("12:60" => None)]
+    #[test_case("1200" => None)]
+    #[test_case("-1:00" => None)]
+    fn time(value: &str) -> Option<String> {
+        parse_time(value).ok()
+    }
+
+    #[test_case("UTC" => Some("UTC".into()))]
+    #[test_case("Utc+01" => Some("UTC+01:00".into()))]
+    #[test_case("UTC+5:30" => Some("UTC+05:30".into()))]
+    #[test_case("UTC-08" => Some("UTC-08:00".into()))]
+    #[test_case("UTC+15:00" => None)]
+    #[test_case("UTC5" => None)]
+    #[test_case("Europe/Berlin" => None)]
+    fn timezone(value: &str) -> Option<String> {
+        parse_timezone(value).ok()
+    }
+}
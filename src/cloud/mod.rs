@@ -1,5 +1,7 @@
 pub mod auth;
 pub mod client;
 pub mod main;
+pub mod maintenance;
 pub mod ops;
 pub mod options;
+pub mod secrets;
@@ -1,35 +1,57 @@
-use std::collections::HashMap;
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashMap};
+use std::env;
 use std::fs;
 use std::io;
 use std::path::PathBuf;
 use std::time::{Duration, Instant};
 
+use anyhow::Context;
 use async_std::task;
 use colorful::Colorful;
 use edgedb_client::credentials::Credentials;
 use edgedb_client::Builder;
+use futures_util::future::join_all;
+use indicatif::{HumanBytes, ProgressBar, ProgressStyle};
 
 use crate::cloud::auth;
-use crate::cloud::client::CloudClient;
+use crate::cloud::client::{all_profiles, ApiError, CloudClient, NotAuthenticated};
+use crate::cloud::maintenance::{self, MaintenanceWindow};
+use crate::cloud::options as cloud_options;
 use crate::commands::ExitCode;
 use crate::credentials;
 use crate::options::CloudOptions;
 use crate::portable::local::is_valid_name;
+use crate::portable::repository::Query;
 use crate::print::{self, echo, err_marker, Highlight};
 use crate::question;
 use crate::table::{self, Cell, Row, Table};
 
 const INSTANCE_CREATION_WAIT_TIME: Duration = Duration::from_secs(5 * 60);
 const INSTANCE_CREATION_POLLING_INTERVAL : Duration = Duration::from_secs(1);
+/// Consecutive failed status checks tolerated while waiting for the instance
+const MAX_POLL_FAILURES: u32 = 5;
+/// Default database and user name, same as for local instances
+const DEFAULT_NAME: &str = "edgedb";
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub struct CloudInstance {
-    id: String,
-    name: String,
+    pub id: String,
+    pub name: String,
     dsn: String,
     status: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     tls_ca: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    org_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    org_slug: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    region: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tier: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    version: Option<String>,
 }
 
 #[derive(Debug, serde::Serialize)]
@@ -37,6 +59,10 @@ struct InstanceStatus {
     cloud_instance: CloudInstance,
     credentials: Option<Credentials>,
     instance_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    profile: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    maintenance_window: Option<MaintenanceWindow>,
 }
 
 impl InstanceStatus {
@@ -45,6 +71,32 @@ impl InstanceStatus {
             cloud_instance,
             credentials: None,
             instance_name: None,
+            profile: None,
+            maintenance_window: None,
+        }
+    }
+
+    /// Fetches maintenance window, failure is not fatal as it's only
+    /// displayed as an extra information
+    async fn load_maintenance_window(&mut self, client: &CloudClient) {
+        match maintenance::get_window(client, &self.cloud_instance).await {
+            Ok(window) => self.maintenance_window = window,
+            Err(e) => log::warn!(
+                "Cannot fetch maintenance window of {}: {:#}",
+                self.cloud_instance.name, e,
+            ),
+        }
+    }
+
+    /// Whether stored credentials don't match the current DSN or TLS CA of
+    /// the instance
+    fn credentials_outdated(&self) -> bool {
+        match &self.credentials {
+            Some(creds) => {
+                creds.cloud_original_dsn.as_ref() != Some(&self.cloud_instance.dsn)
+                    || creds.tls_ca != self.cloud_instance.tls_ca
+            }
+            None => false,
         }
     }
 
@@ -53,6 +105,18 @@ impl InstanceStatus {
 
         println!("  Status: {}", self.cloud_instance.status);
         println!("  ID: {}", self.cloud_instance.id);
+        if let Some(region) = &self.cloud_instance.region {
+            println!("  Region: {}", region);
+        }
+        if let Some(tier) = &self.cloud_instance.tier {
+            println!("  Tier: {}", tier);
+        }
+        if let Some(profile) = &self.profile {
+            println!("  Profile: {}", profile);
+        }
+        if let Some(window) = &self.maintenance_window {
+            println!("  Maintenance Window: {}", window.display());
+        }
         if let Some(name) = &self.instance_name {
             println!("  Local Instance: {}", name);
         }
@@ -61,32 +125,110 @@ impl InstanceStatus {
                 println!("  DSN: {}", dsn);
             }
         }
+        if self.credentials_outdated() {
+            println!(
+                "  Credentials: outdated, run `edgedb cloud instance refresh {}`",
+                self.cloud_instance.name
+            );
+        }
     }
 }
 
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct InstanceMetrics {
+    cpu_percent: Option<f64>,
+    storage_used: Option<u64>,
+    storage_total: Option<u64>,
+    connections: Option<u64>,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct MetricsSummary {
+    name: String,
+    status: String,
+    metrics: Option<InstanceMetrics>,
+}
+
 #[derive(Debug, serde::Deserialize)]
+struct LogEntry {
+    timestamp: String,
+    message: String,
+}
+
+/// Position of the `logs --follow` poller
+#[derive(Debug, Default)]
+struct LogCursor {
+    /// Timestamp of the last returned entry
+    timestamp: Option<String>,
+    /// Messages already returned with `timestamp`
+    seen: Vec<String>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct CloudVersion {
+    pub version: String,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Organization {0:?} not found; check the name with \
+         `edgedb cloud orgs list`")]
+pub struct OrgNotFound(String);
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub struct Org {
     pub id: String,
     pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slug: Option<String>,
 }
 
 #[derive(Debug, serde::Serialize)]
 pub struct CloudInstanceCreate {
     pub name: String,
     pub org: String,
-    // #[serde(skip_serializing_if = "Option::is_none")]
-    // pub version: Option<String>,
-    // #[serde(skip_serializing_if = "Option::is_none")]
-    // pub default_database: Option<String>,
-    // #[serde(skip_serializing_if = "Option::is_none")]
-    // pub default_user: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_connections: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_database: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_user: Option<String>,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct CreateResult<'a> {
+    instance: &'a CloudInstance,
+    credentials_path: &'a PathBuf,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct CloudInstanceResize {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tier: Option<String>,
+}
+
+impl CloudInstance {
+    /// Credentials to connect to the instance, keyed by its ID
+    pub async fn as_credentials(&self) -> anyhow::Result<Credentials> {
+        let mut creds = Builder::uninitialized()
+            .read_dsn(&self.dsn)
+            .await?
+            .as_credentials()?;
+        creds.tls_ca = self.tls_ca.clone();
+        creds.cloud_instance_id = Some(self.id.clone());
+        creds.cloud_original_dsn = Some(self.dsn.clone());
+        Ok(creds)
+    }
 }
 
 pub async fn find_cloud_instance_by_name(
     name: &str,
     client: &CloudClient,
 ) -> anyhow::Result<Option<CloudInstance>> {
-    let instances: Vec<CloudInstance> = client.get("instances/").await?;
+    let instances: Vec<CloudInstance> = client.get_all("instances/").await?;
     if let Some(instance) = instances
         .into_iter()
         .find(|instance| instance.name.eq(&name))
@@ -97,62 +239,215 @@ pub async fn find_cloud_instance_by_name(
     }
 }
 
+/// Whether the instance belongs to the organization
+///
+/// Instances that report neither ID nor slug of their organization never
+/// match, as names are only unique within an organization.
+fn in_org(instance: &CloudInstance, org: &Org) -> bool {
+    instance.org_id.as_ref() == Some(&org.id)
+        || (org.slug.is_some() && instance.org_slug == org.slug)
+}
+
+/// Finds instance by name in the specified organization
+async fn find_org_instance(
+    name: &str,
+    org: &Org,
+    client: &CloudClient,
+) -> anyhow::Result<Option<CloudInstance>> {
+    let instances: Vec<CloudInstance> = client.get_all("instances/").await?;
+    Ok(instances
+        .into_iter()
+        .find(|instance| instance.name == name && in_org(instance, org)))
+}
+
+/// Same as `find_cloud_instance_by_name` but fails if instance doesn't exist
+pub async fn get_cloud_instance_by_name(
+    name: &str,
+    client: &CloudClient,
+) -> anyhow::Result<CloudInstance> {
+    find_cloud_instance_by_name(name, client)
+        .await?
+        .with_context(|| format!("No such Cloud instance named {}", name))
+}
+
+/// Whether an instance returned by the API can be connected to
+///
+/// Some backends publish the DSN before the instance is ready, so both DSN
+/// and `available` status are required unless `early_dsn` is set, which
+/// restores the old behavior of trusting the DSN alone.
+fn instance_ready(instance: &CloudInstance, early_dsn: bool) -> anyhow::Result<bool> {
+    if instance.dsn != "" && (early_dsn || instance.status == "available") {
+        return Ok(true);
+    }
+    if instance.status != "available" && instance.status != "creating" {
+        anyhow::bail!(
+            "Failed to create EdgeDB Cloud instance: {}",
+            instance.status
+        );
+    }
+    Ok(false)
+}
+
+/// Whether an instance is available again after a change was requested
+///
+/// Right after the request the instance may still be reported as
+/// `available` with the old settings, so it's considered updated only
+/// after it has left the `available` status (tracked in `transitioned`)
+/// or when it already reports the requested tier and version.
+fn instance_updated(
+    instance: &CloudInstance,
+    request: &CloudInstanceResize,
+    transitioned: &Cell<bool>,
+) -> anyhow::Result<bool> {
+    match &instance.status[..] {
+        "available" => Ok(transitioned.get() || resize_applied(instance, request)),
+        "failed" => anyhow::bail!(
+            "Failed to update EdgeDB Cloud instance: {}",
+            instance.status
+        ),
+        _ => {
+            transitioned.set(true);
+            Ok(false)
+        }
+    }
+}
+
+fn resize_applied(instance: &CloudInstance, request: &CloudInstanceResize) -> bool {
+    let matches = |requested: &Option<String>, actual: &Option<String>| {
+        requested.is_none() || requested == actual
+    };
+    matches(&request.tier, &instance.tier)
+        && matches(&request.version, &instance.version)
+}
+
+/// Reads duration in seconds from the environment variable
+fn env_seconds(name: &str, default: Duration) -> Duration {
+    match env::var(name) {
+        Ok(val) => match val.parse::<u64>() {
+            Ok(secs) if secs > 0 => Duration::from_secs(secs),
+            _ => {
+                log::warn!("Invalid {} {:?}, using default of {} seconds",
+                           name, val, default.as_secs());
+                default
+            }
+        },
+        Err(_) => default,
+    }
+}
+
+/// Time to wait for the instance to become ready, unless `--timeout` is
+/// specified
+///
+/// Overridden by `EDGEDB_CLOUD_CREATE_TIMEOUT` (in seconds).
+fn creation_timeout() -> Duration {
+    env_seconds("EDGEDB_CLOUD_CREATE_TIMEOUT", INSTANCE_CREATION_WAIT_TIME)
+}
+
+/// Overridden by `EDGEDB_CLOUD_POLL_INTERVAL` (in seconds).
+fn polling_interval() -> Duration {
+    env_seconds("EDGEDB_CLOUD_POLL_INTERVAL",
+                INSTANCE_CREATION_POLLING_INTERVAL)
+}
+
 async fn wait_instance_create(
+    instance: CloudInstance,
+    client: &CloudClient,
+    quiet: bool,
+    timeout: Option<Duration>,
+) -> anyhow::Result<CloudInstance> {
+    let early_dsn = env::var_os("EDGEDB_CLOUD_EARLY_DSN").is_some();
+    wait_instance(instance, client, quiet, timeout, "creation",
+                  |instance| instance_ready(instance, early_dsn)).await
+}
+
+/// Polls the instance until `ready` returns true or timeout expires
+async fn wait_instance(
     mut instance: CloudInstance,
     client: &CloudClient,
     quiet: bool,
+    timeout: Option<Duration>,
+    action: &str,
+    ready: impl Fn(&CloudInstance) -> anyhow::Result<bool>,
 ) -> anyhow::Result<CloudInstance> {
-    if !quiet && instance.status == "creating" {
-        print::echo!("Waiting for EdgeDB Cloud instance creation...");
-    }
-    let deadline = Instant::now() + INSTANCE_CREATION_WAIT_TIME;
-    while Instant::now() < deadline {
-        if instance.dsn != "" {
-            return Ok(instance);
-        }
-        if instance.status != "available" && instance.status != "creating" {
-            anyhow::bail!(
-                "Failed to create EdgeDB Cloud instance: {}",
-                instance.status
-            );
-        }
-        if instance.status == "creating" {
-            task::sleep(INSTANCE_CREATION_POLLING_INTERVAL).await;
+    let bar = if !quiet && !ready(&instance)? {
+        let bar = ProgressBar::new_spinner();
+        bar.set_style(
+            ProgressStyle::default_spinner()
+            .template("{spinner} {msg} [{elapsed}]"));
+        bar.enable_steady_tick(100);
+        bar
+    } else {
+        ProgressBar::hidden()
+    };
+    let timeout = timeout.unwrap_or_else(creation_timeout);
+    let interval = polling_interval();
+    let deadline = Instant::now() + timeout;
+    let mut failures = 0;
+    let result = async {
+        while Instant::now() < deadline {
+            bar.set_message(format!(
+                "Waiting for EdgeDB Cloud instance {} (status: {})",
+                action, instance.status));
+            if ready(&instance)? {
+                return Ok(true);
+            }
+            task::sleep(interval).await;
+            match client.get(format!("instances/{}", instance.id)).await {
+                Ok(updated) => {
+                    instance = updated;
+                    failures = 0;
+                }
+                Err(e) if failures < MAX_POLL_FAILURES
+                    && !e.is::<NotAuthenticated>()
+                => {
+                    failures += 1;
+                    log::warn!("Cannot check status of instance {}: {:#}",
+                               instance.name, e);
+                }
+                Err(e) => return Err(e),
+            }
         }
-        instance = client.get(format!("instances/{}", instance.id)).await?;
-    }
-    if instance.dsn != "" {
+        ready(&instance)
+    }.await;
+    bar.finish_and_clear();
+    if result? {
         Ok(instance)
     } else {
-        anyhow::bail!("Timed out.")
+        anyhow::bail!("Timed out after {} waiting for instance {:?} \
+                       (last status: {}). Increase the timeout with \
+                       `--timeout` or EDGEDB_CLOUD_CREATE_TIMEOUT.",
+                      humantime::format_duration(timeout),
+                      instance.name, instance.status)
     }
 }
 
-async fn write_credentials(cred_path: &PathBuf, instance: CloudInstance) -> anyhow::Result<()> {
-    let mut creds = Builder::uninitialized()
-        .read_dsn(&instance.dsn)
-        .await?
-        .as_credentials()?;
-    creds.tls_ca = instance.tls_ca;
-    creds.cloud_instance_id = Some(instance.id);
-    creds.cloud_original_dsn = Some(instance.dsn);
-    credentials::write(&cred_path, &creds).await
+async fn write_credentials(cred_path: &PathBuf, instance: &CloudInstance) -> anyhow::Result<()> {
+    credentials::write(&cred_path, &instance.as_credentials().await?).await
 }
 
 pub async fn create_cloud_instance(
     client: &CloudClient,
     instance: &CloudInstanceCreate,
-) -> anyhow::Result<()> {
+    timeout: Option<Duration>,
+) -> anyhow::Result<CloudInstance> {
     let cred_path = credentials::path(&instance.name)?;
     if cred_path.exists() {
         anyhow::bail!("File {} exists; abort.", cred_path.display());
     }
+    // instance doesn't exist yet, so not found can only refer to the org
     let instance: CloudInstance = client
         .post("instances/", serde_json::to_value(instance)?)
+        .await
+        .map_err(|e| match e.downcast_ref::<ApiError>() {
+            Some(api) if api.code == surf::StatusCode::NotFound => {
+                OrgNotFound(instance.org.clone()).into()
+            }
+            _ => e,
+        })?;
+    let instance = wait_instance_create(instance, client, false, timeout)
         .await?;
-    let instance = wait_instance_create(instance, client, false).await?;
-    write_credentials(&cred_path, instance).await?;
-    Ok(())
+    write_credentials(&cred_path, &instance).await?;
+    Ok(instance)
 }
 
 fn ask_name() -> anyhow::Result<String> {
@@ -180,19 +475,26 @@ pub async fn create(
     cmd: &crate::portable::options::Create,
     opts: &crate::options::Options,
 ) -> anyhow::Result<()> {
-    let client = CloudClient::new(&opts.cloud_options)?;
-    client.ensure_authenticated(false)?;
-    // let version = Query::from_options(cmd.nightly, &cmd.version)?;
-    let orgs: Vec<Org> = client.get("orgs/").await?;
-    let org_id = if let Some(name) = &cmd.cloud_org {
-        if let Some(org) = orgs.iter().find(|org| org.name.eq(name)) {
-            org.id.clone()
+    let client = auth::authenticated_client(&opts.cloud_options).await?;
+    // server picks the default version unless one is requested explicitly
+    let version = if cmd.nightly || cmd.version.is_some() {
+        let query = Query::from_options(cmd.nightly, &cmd.version)?;
+        Some(query.display().to_string())
+    } else {
+        None
+    };
+    let orgs: Vec<Org> = client.get_all("orgs/").await?;
+    let org = if let Some(name) = &cmd.cloud_org {
+        if let Some(org) = orgs.iter().find(|org| {
+            org.name.eq(name) || org.slug.as_ref() == Some(name)
+        }) {
+            org
         } else {
-            anyhow::bail!("Organization {} not found", name);
+            return Err(OrgNotFound(name.clone()).into());
         }
     } else {
         // TODO: use default organization
-        orgs[0].id.clone()
+        orgs.first().context("no EdgeDB Cloud organizations available")?
     };
     let name = if let Some(name) = &cmd.name {
         name.to_owned()
@@ -203,19 +505,72 @@ pub async fn create(
     } else {
         ask_name()?
     };
+    let timeout = cmd.timeout.map(Duration::from_secs);
+    if cmd.if_not_exists {
+        if let Some(existing) = find_org_instance(&name, org, &client).await? {
+            let existing = wait_instance_create(existing, &client, false, timeout)
+                .await?;
+            let cred_path = credentials::path(&name)?;
+            if !cred_path.exists() {
+                write_credentials(&cred_path, &existing).await?;
+            }
+            if cmd.json {
+                let result = CreateResult {
+                    instance: &existing,
+                    credentials_path: &cred_path,
+                };
+                println!("{}", serde_json::to_string_pretty(&result)?);
+                return Ok(());
+            }
+            print::echo!("EdgeDB Cloud instance", name.emphasize(), "already exists.");
+            print::echo!("To connect to the instance run:");
+            print::echo!("  edgedb -I", name);
+            return Ok(());
+        }
+    }
     let instance = CloudInstanceCreate {
         name: name.clone(),
-        org: org_id
-        // version: Some(format!("{}", version.display())),
-        // default_database: Some(cmd.default_database.clone()),
-        // default_user: Some(cmd.default_user.clone()),
+        org: org.id.clone(),
+        max_connections: cmd.max_connections,
+        version,
+        // only sent when changed, so that older API servers keep working
+        default_database: Some(cmd.default_database.clone())
+            .filter(|db| db != DEFAULT_NAME),
+        default_user: Some(cmd.default_user.clone())
+            .filter(|user| user != DEFAULT_NAME),
     };
-    create_cloud_instance(&client, &instance).await?;
+    let created = create_cloud_instance(&client, &instance, timeout)
+        .await
+        .with_context(|| {
+            if let Some(version) = &instance.version {
+                format!("cannot create EdgeDB Cloud instance \
+                         (note: version {} may be unsupported, run \
+                         `edgedb cloud instance versions` to list \
+                         available ones)", version)
+            } else if instance.max_connections.is_some() {
+                "cannot create EdgeDB Cloud instance \
+                 (note: maximum connections may be limited by the instance tier)"
+                    .into()
+            } else {
+                "cannot create EdgeDB Cloud instance".into()
+            }
+        })?;
+    if cmd.json {
+        let result = CreateResult {
+            instance: &created,
+            credentials_path: &credentials::path(&name)?,
+        };
+        println!("{}", serde_json::to_string_pretty(&result)?);
+        return Ok(());
+    }
     print::echo!(
         "EdgeDB Cloud instance",
         name.emphasize(),
         "is up and running."
     );
+    if let Some(max_connections) = instance.max_connections {
+        print::echo!("Maximum connections:", max_connections);
+    }
     print::echo!("To connect to the instance run:");
     print::echo!("  edgedb -I", name);
     Ok(())
@@ -281,7 +636,7 @@ pub async fn link(
     };
     let instance = if let Some(instance) = find_cloud_instance_by_name(&cloud_name, &client).await?
     {
-        wait_instance_create(instance, &client, cmd.quiet).await?
+        wait_instance_create(instance, &client, cmd.quiet, None).await?
     } else {
         anyhow::bail!("No such Cloud instance named {}", cloud_name);
     };
@@ -332,7 +687,7 @@ pub async fn link(
         }
     };
 
-    write_credentials(&cred_path, instance).await?;
+    write_credentials(&cred_path, &instance).await?;
     if !cmd.quiet {
         let mut msg = "Successfully linked to EdgeDB Cloud instance.".to_string();
         if print::use_color() {
@@ -350,48 +705,239 @@ pub async fn link(
 
 async fn destroy(instance_id: &str, options: &CloudOptions) -> anyhow::Result<()> {
     log::info!("Destroying EdgeDB Cloud instance: {}", instance_id);
-    let client = CloudClient::new(options)?;
-    client.ensure_authenticated(false)?;
+    let client = auth::authenticated_client(options).await?;
     let _: CloudInstance = client.delete(format!("instances/{}", instance_id)).await?;
     Ok(())
 }
 
+fn read_cloud_instance_id(cred_path: &PathBuf) -> anyhow::Result<Option<String>> {
+    let file = io::BufReader::new(fs::File::open(cred_path)?);
+    let credentials: Credentials = serde_json::from_reader(file)?;
+    Ok(credentials.cloud_instance_id)
+}
+
 pub fn try_to_destroy(
     cred_path: &PathBuf,
     options: &crate::options::Options,
 ) -> anyhow::Result<()> {
-    let file = io::BufReader::new(fs::File::open(cred_path)?);
-    let credentials: Credentials = serde_json::from_reader(file)?;
-    if let Some(instance_id) = credentials.cloud_instance_id {
+    if let Some(instance_id) = read_cloud_instance_id(cred_path)? {
         task::block_on(destroy(&instance_id, &options.cloud_options))?;
     }
     Ok(())
 }
 
+/// Asks user to type the name of the EdgeDB Cloud instance to confirm
+/// destroying it
+///
+/// Returns `None` if credentials don't refer to an EdgeDB Cloud instance.
+/// Fails rather than destroying the instance if stdin is not a terminal.
+/// The cloud name of the instance is only fetched to show it in the prompt,
+/// if that fails the local `name` is used instead.
+pub fn confirm_destroy(
+    name: &str,
+    cred_path: &PathBuf,
+    options: &crate::options::Options,
+) -> anyhow::Result<Option<bool>> {
+    if !cred_path.exists() {
+        return Ok(None);
+    }
+    let instance_id = match read_cloud_instance_id(cred_path)? {
+        Some(instance_id) => instance_id,
+        None => return Ok(None),
+    };
+    if !atty::is(atty::Stream::Stdin) {
+        anyhow::bail!(
+            "Refusing to destroy EdgeDB Cloud instance {:?} without \
+             confirmation; use `--force` or `--non-interactive`",
+            name
+        );
+    }
+    let cloud_name = match task::block_on(async {
+        let client = auth::authenticated_client(&options.cloud_options).await?;
+        let instance: CloudInstance =
+            client.get(format!("instances/{}", instance_id)).await?;
+        Ok::<_, anyhow::Error>(instance.name)
+    }) {
+        Ok(cloud_name) => cloud_name,
+        Err(e) => {
+            log::warn!("Cannot fetch EdgeDB Cloud instance {}: {:#}",
+                       instance_id, e);
+            name.to_string()
+        }
+    };
+    echo!(
+        "This will destroy EdgeDB Cloud instance",
+        cloud_name.emphasize(),
+        "and all of its data."
+    );
+    let answer = question::String::new("Type the name of the instance to confirm")
+        .ask()?;
+    Ok(Some(answer == cloud_name))
+}
+
+pub fn print_destroy_plan(
+    cred_path: &PathBuf,
+    options: &crate::options::Options,
+) -> anyhow::Result<()> {
+    if let Some(instance_id) = read_cloud_instance_id(cred_path)? {
+        // dry run must not prompt for login, so failures are not fatal
+        let lookup = task::block_on(get_instance_by_id(
+            &instance_id,
+            &options.cloud_options,
+        ));
+        match lookup {
+            Ok(instance) => echo!(
+                "Would destroy EdgeDB Cloud instance",
+                instance.name.emphasize(),
+                format_args!("(id: {})", instance.id)
+            ),
+            Err(e) => {
+                log::warn!("Cannot look up EdgeDB Cloud instance {}: {:#}",
+                           instance_id, e);
+                echo!(
+                    "Would destroy EdgeDB Cloud instance",
+                    format_args!("(id: {})", instance_id)
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Fetches instance using stored credentials, never asks to log in
+async fn get_instance_by_id(
+    instance_id: &str,
+    options: &CloudOptions,
+) -> anyhow::Result<CloudInstance> {
+    let client = CloudClient::new(options)?;
+    if !client.is_logged_in {
+        anyhow::bail!("not logged in");
+    }
+    client.get(format!("instances/{}", instance_id)).await
+}
+
+async fn list_profile(
+    options: &CloudOptions,
+    extended: bool,
+) -> anyhow::Result<Vec<InstanceStatus>> {
+    let client = CloudClient::new(options)?;
+    if !client.is_logged_in {
+        anyhow::bail!("not logged in, run `edgedb cloud login` first");
+    }
+    let cloud_instances: Vec<CloudInstance> = client.get_all("instances/").await?;
+    let profile = options.cloud_profile.as_deref().unwrap_or("default");
+    let mut statuses: Vec<_> = cloud_instances
+        .into_iter()
+        .map(|inst| InstanceStatus {
+            profile: Some(profile.into()),
+            ..InstanceStatus::from_cloud_instance(inst)
+        })
+        .collect();
+    if extended {
+        for status in &mut statuses {
+            status.load_maintenance_window(&client).await;
+        }
+    }
+    Ok(statuses)
+}
+
+async fn read_credentials(name: &str) -> anyhow::Result<Credentials> {
+    let path = credentials::path(name)?;
+    let data = async_std::fs::read(&path)
+        .await
+        .with_context(|| format!("cannot read {:?}", path))?;
+    Ok(serde_json::from_slice(&data).with_context(|| format!("cannot decode {:?}", path))?)
+}
+
+/// Reads local credentials concurrently and maps cloud instance IDs to
+/// instance names and credentials
+///
+/// Files that can't be read are skipped with a warning.
+async fn cloud_credentials() -> anyhow::Result<HashMap<String, (String, Credentials)>> {
+    let tasks = credentials::all_instance_names()?
+        .into_iter()
+        .map(|name| {
+            task::spawn(async move {
+                let result = read_credentials(&name).await;
+                (name, result)
+            })
+        })
+        .collect::<Vec<_>>();
+    let mut result = HashMap::new();
+    for (name, creds) in join_all(tasks).await {
+        match creds {
+            Ok(creds) => {
+                if let Some(id) = creds.cloud_instance_id.clone() {
+                    result.insert(id, (name, creds));
+                }
+            }
+            Err(e) => log::warn!("Skipping credentials of {:?}: {:#}", name, e),
+        }
+    }
+    Ok(result)
+}
+
 pub async fn list(
     cmd: &crate::portable::options::List,
     opts: &crate::options::Options,
 ) -> anyhow::Result<()> {
-    let client = CloudClient::new(&opts.cloud_options)?;
-    client.ensure_authenticated(false)?;
-    let cloud_instances: Vec<CloudInstance> = client.get("instances/").await?;
-    let mut instances = cloud_instances
-        .into_iter()
-        .map(|inst| (inst.id.clone(), InstanceStatus::from_cloud_instance(inst)))
-        .collect::<HashMap<String, InstanceStatus>>();
-    for name in credentials::all_instance_names()? {
-        let file = io::BufReader::new(fs::File::open(credentials::path(&name)?)?);
-        let creds: Credentials = serde_json::from_reader(file)?;
-        if let Some(id) = &creds.cloud_instance_id {
-            if let Some(instance) = instances.get_mut(id) {
-                (*instance).instance_name = Some(name);
-                (*instance).credentials = Some(creds);
+    let mut instances = HashMap::new();
+    if cmd.all_profiles {
+        for profile in all_profiles()? {
+            let options = CloudOptions {
+                cloud_profile: profile,
+                ..opts.cloud_options.clone()
+            };
+            match list_profile(&options, cmd.extended).await {
+                Ok(items) => instances.extend(
+                    items
+                        .into_iter()
+                        .map(|inst| (inst.cloud_instance.id.clone(), inst)),
+                ),
+                Err(e) => print::warn(format!(
+                    "Cannot list instances of profile {:?}: {:#}",
+                    options.cloud_profile.as_deref().unwrap_or("default"),
+                    e
+                )),
             }
         }
+    } else {
+        let client = auth::authenticated_client(&opts.cloud_options).await?;
+        let cloud_instances: Vec<CloudInstance> = client.get_all("instances/").await?;
+        for inst in cloud_instances {
+            let mut status = InstanceStatus::from_cloud_instance(inst);
+            if cmd.extended {
+                status.load_maintenance_window(&client).await;
+            }
+            instances.insert(status.cloud_instance.id.clone(), status);
+        }
     }
+    for (id, (name, creds)) in cloud_credentials().await? {
+        if let Some(instance) = instances.get_mut(&id) {
+            (*instance).instance_name = Some(name);
+            (*instance).credentials = Some(creds);
+        }
+    }
+    instances.retain(|_, instance| {
+        let inst = &instance.cloud_instance;
+        cmd.org.as_ref().map_or(true, |org| inst.org_slug.as_ref() == Some(org))
+            && cmd.status.as_ref().map_or(true, |status| &inst.status == status)
+    });
+    if cmd.summary {
+        let summary = Summary::new(instances.values().map(|i| &i.cloud_instance));
+        if cmd.json {
+            println!("{}", serde_json::to_string_pretty(&summary)?);
+        } else {
+            summary.print();
+        }
+        return Ok(());
+    }
+    let prometheus = cmd.format.as_deref() == Some("prometheus");
     if instances.is_empty() {
         if cmd.json {
             println!("[]");
+        } else if prometheus {
+            print_prometheus(&[]);
         } else if !cmd.quiet {
             print::warn("No instances found");
         }
@@ -402,6 +948,10 @@ pub async fn list(
             "{}",
             serde_json::to_string_pretty(&instances.into_values().collect::<Vec<_>>())?
         );
+    } else if prometheus {
+        let mut instances = instances.values().collect::<Vec<_>>();
+        instances.sort_by(|a, b| a.cloud_instance.name.cmp(&b.cloud_instance.name));
+        print_prometheus(&instances);
     } else if cmd.debug {
         for instance in instances.values() {
             println!("{:#?}", instance);
@@ -411,29 +961,122 @@ pub async fn list(
             instance.print_extended();
         }
     } else {
+        let mut titles = vec![
+            "Kind", "Name", "Cloud Name", "Status", "Region", "Tier",
+        ];
+        if cmd.all_profiles {
+            titles.push("Profile");
+        }
         let mut table = Table::new();
         table.set_format(*table::FORMAT);
         table.set_titles(Row::new(
-            ["Kind", "Name", "Cloud Name", "Status"]
-                .iter()
-                .map(|x| table::header_cell(x))
-                .collect(),
+            titles.iter().map(|x| table::header_cell(x)).collect(),
         ));
         for instance in instances.values() {
-            table.add_row(Row::new(vec![
+            let mut row = vec![
                 Cell::new("cloud"),
                 Cell::new(instance.instance_name.as_deref().unwrap_or("-")),
                 Cell::new(&instance.cloud_instance.name),
                 Cell::new(&instance.cloud_instance.status),
-            ]));
+                Cell::new(instance.cloud_instance.region.as_deref().unwrap_or("")),
+                Cell::new(instance.cloud_instance.tier.as_deref().unwrap_or("")),
+            ];
+            if cmd.all_profiles {
+                row.push(Cell::new(instance.profile.as_deref().unwrap_or("-")));
+            }
+            table.add_row(Row::new(row));
         }
         table.printstd();
+        for instance in instances.values() {
+            if instance.credentials_outdated() {
+                print::warn(format!(
+                    "Credentials of {} are outdated, run \
+                     `edgedb cloud instance refresh {}`",
+                    instance.instance_name.as_deref().unwrap_or("-"),
+                    instance.cloud_instance.name
+                ));
+            }
+        }
     }
     Ok(())
 }
 
+#[derive(Debug, serde::Serialize)]
+struct Summary<'a> {
+    total: usize,
+    by_status: BTreeMap<&'a str, usize>,
+    by_org: BTreeMap<&'a str, usize>,
+}
+
+impl<'a> Summary<'a> {
+    fn new(instances: impl Iterator<Item = &'a CloudInstance>) -> Summary<'a> {
+        let mut summary = Summary {
+            total: 0,
+            by_status: BTreeMap::new(),
+            by_org: BTreeMap::new(),
+        };
+        for inst in instances {
+            summary.total += 1;
+            *summary.by_status.entry(&inst.status[..]).or_insert(0) += 1;
+            let org = inst.org_slug.as_deref().unwrap_or("-");
+            *summary.by_org.entry(org).or_insert(0) += 1;
+        }
+        summary
+    }
+
+    fn print(&self) {
+        fn counts(items: &BTreeMap<&str, usize>) -> String {
+            items
+                .iter()
+                .map(|(key, count)| format!("{}: {}", key, count))
+                .collect::<Vec<_>>()
+                .join(", ")
+        }
+        echo!("Total instances:", self.total);
+        if self.total > 0 {
+            echo!("By status:", counts(&self.by_status));
+            echo!("By organization:", counts(&self.by_org));
+        }
+    }
+}
+
+fn prometheus_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn print_prometheus(instances: &[&InstanceStatus]) {
+    println!("# HELP edgedb_cloud_instance_status \
+              EdgeDB Cloud instance, always 1.");
+    println!("# TYPE edgedb_cloud_instance_status gauge");
+    let mut by_status = BTreeMap::new();
+    for instance in instances {
+        let inst = &instance.cloud_instance;
+        println!(
+            "edgedb_cloud_instance_status\
+             {{org=\"{}\",name=\"{}\",status=\"{}\"}} 1",
+            prometheus_label(inst.org_slug.as_deref().unwrap_or("")),
+            prometheus_label(&inst.name),
+            prometheus_label(&inst.status),
+        );
+        *by_status.entry(&inst.status[..]).or_insert(0) += 1;
+    }
+    println!("# HELP edgedb_cloud_instances \
+              Number of EdgeDB Cloud instances by status.");
+    println!("# TYPE edgedb_cloud_instances gauge");
+    for (status, count) in by_status {
+        println!(
+            "edgedb_cloud_instances{{status=\"{}\"}} {}",
+            prometheus_label(status),
+            count
+        );
+    }
+}
+
 pub async fn ask_link_existing_cloud_instance(client: &CloudClient) -> anyhow::Result<String> {
-    let cloud_instances: Vec<CloudInstance> = client.get("instances/").await?;
+    let cloud_instances: Vec<CloudInstance> = client.get_all("instances/").await?;
     let mut cloud_instances = cloud_instances
         .into_iter()
         .map(|inst| (inst.name.clone(), inst))
@@ -453,9 +1096,9 @@ pub async fn ask_link_existing_cloud_instance(client: &CloudClient) -> anyhow::R
                 target_name
             ));
         } else if let Some(inst) = cloud_instances.remove(&target_name) {
-            let inst = wait_instance_create(inst, client, false).await?;
+            let inst = wait_instance_create(inst, client, false, None).await?;
             let cred_path = credentials::path(&target_name)?;
-            write_credentials(&cred_path, inst).await?;
+            write_credentials(&cred_path, &inst).await?;
             return Ok(target_name);
         } else {
             print::error(format!("Cloud instance {:?} doesn't exist", target_name));
@@ -472,10 +1115,571 @@ pub async fn link_existing_cloud_instance(client: &CloudClient, name: &str) -> a
         );
     }
     if let Some(inst) = find_cloud_instance_by_name(name, client).await? {
-        let inst = wait_instance_create(inst, client, false).await?;
-        write_credentials(&cred_path, inst).await?;
+        let inst = wait_instance_create(inst, client, false, None).await?;
+        write_credentials(&cred_path, &inst).await?;
         Ok(())
     } else {
         anyhow::bail!(format!("Cloud instance {:?} doesn't exist", name));
     }
 }
+
+pub async fn resize(
+    cmd: &cloud_options::Resize,
+    options: &CloudOptions,
+) -> anyhow::Result<()> {
+    let client = auth::authenticated_client(options).await?;
+    let instance = get_cloud_instance_by_name(&cmd.name, &client).await?;
+    if let Some(version) = &cmd.version {
+        let versions = available_versions(&client, Some(&instance)).await?;
+        if !versions.iter().any(|v| &v.version == version) {
+            anyhow::bail!(
+                "Instance {} cannot be upgraded to version {}; \
+                 run `edgedb cloud instance versions {}` to list \
+                 available upgrades",
+                instance.name, version, instance.name
+            );
+        }
+    }
+    let request = CloudInstanceResize {
+        version: cmd.version.clone(),
+        tier: cmd.tier.clone(),
+    };
+    let instance: CloudInstance = client
+        .put(
+            format!("instances/{}", instance.id),
+            serde_json::to_value(&request)?,
+        )
+        .await
+        .context("cannot change EdgeDB Cloud instance")?;
+    let transitioned = Cell::new(false);
+    wait_instance(instance, &client, false,
+                  cmd.timeout.map(Duration::from_secs), "update",
+                  |instance| instance_updated(instance, &request, &transitioned)).await?;
+    print::echo!(
+        "EdgeDB Cloud instance",
+        cmd.name.emphasize(),
+        "is up and running."
+    );
+    Ok(())
+}
+
+pub async fn export(
+    cmd: &cloud_options::Export,
+    options: &CloudOptions,
+) -> anyhow::Result<()> {
+    let client = auth::authenticated_client(options).await?;
+    let instance = get_cloud_instance_by_name(&cmd.name, &client).await?;
+    let creds = instance.as_credentials().await?;
+    if let Some(path) = &cmd.output {
+        credentials::write(path, &creds)
+            .await
+            .with_context(|| format!("cannot write {:?}", path))?;
+        print::echo!(
+            "Credentials of",
+            instance.name.emphasize(),
+            "are written to",
+            path.display()
+        );
+    } else {
+        println!("{}", serde_json::to_string_pretty(&creds)?);
+    }
+    Ok(())
+}
+
+pub async fn refresh(
+    cmd: &cloud_options::Refresh,
+    options: &CloudOptions,
+) -> anyhow::Result<()> {
+    let client = auth::authenticated_client(options).await?;
+    let instance = get_cloud_instance_by_name(&cmd.name, &client).await?;
+    let (name, _) = cloud_credentials()
+        .await?
+        .remove(&instance.id)
+        .with_context(|| {
+            format!(
+                "Cloud instance {} is not linked, \
+                 run `edgedb instance link --cloud {}`",
+                instance.name, instance.name
+            )
+        })?;
+    write_credentials(&credentials::path(&name)?, &instance).await?;
+    print::echo!("Credentials of", name.emphasize(), "are updated.");
+    Ok(())
+}
+
+pub async fn status(
+    cmd: &cloud_options::Status,
+    options: &CloudOptions,
+) -> anyhow::Result<()> {
+    let client = auth::authenticated_client(options).await?;
+    let instance = get_cloud_instance_by_name(&cmd.name, &client).await?;
+    let mut status = InstanceStatus::from_cloud_instance(instance);
+    status.load_maintenance_window(&client).await;
+    if let Some((name, creds)) = cloud_credentials()
+        .await?
+        .remove(&status.cloud_instance.id)
+    {
+        status.instance_name = Some(name);
+        status.credentials = Some(creds);
+    }
+    if cmd.json {
+        println!("{}", serde_json::to_string_pretty(&status)?);
+    } else {
+        status.print_extended();
+    }
+    Ok(())
+}
+
+pub async fn metrics(
+    cmd: &cloud_options::Metrics,
+    options: &CloudOptions,
+) -> anyhow::Result<()> {
+    let client = auth::authenticated_client(options).await?;
+    let instance = get_cloud_instance_by_name(&cmd.name, &client).await?;
+    // suspended and creating instances don't expose metrics
+    let metrics = if instance.status == "available" {
+        Some(
+            client
+                .get(format!("instances/{}/metrics", instance.id))
+                .await?,
+        )
+    } else {
+        None
+    };
+    let summary = MetricsSummary {
+        name: instance.name,
+        status: instance.status,
+        metrics,
+    };
+    if cmd.json {
+        println!("{}", serde_json::to_string_pretty(&summary)?);
+        return Ok(());
+    }
+    let metrics = if let Some(metrics) = &summary.metrics {
+        metrics
+    } else {
+        print::warn(format!(
+            "Metrics are not available for instance {} in status {:?}.",
+            summary.name, summary.status
+        ));
+        return Ok(());
+    };
+    let na = || String::from("N/A");
+    let storage = match (metrics.storage_used, metrics.storage_total) {
+        (Some(used), Some(total)) => {
+            format!("{} / {}", HumanBytes(used), HumanBytes(total))
+        }
+        (Some(used), None) => HumanBytes(used).to_string(),
+        _ => na(),
+    };
+    table::settings(&[
+        ("Instance", &summary.name),
+        ("Status", &summary.status),
+        (
+            "CPU",
+            &metrics
+                .cpu_percent
+                .map(|cpu| format!("{:.1}%", cpu))
+                .unwrap_or_else(na),
+        ),
+        ("Storage", &storage),
+        (
+            "Connections",
+            &metrics
+                .connections
+                .map(|n| n.to_string())
+                .unwrap_or_else(na),
+        ),
+    ]);
+    Ok(())
+}
+
+impl LogCursor {
+    /// Returns entries that weren't returned before and advances the cursor
+    ///
+    /// Polling uses an inclusive `since`, so that entries which share the
+    /// last timestamp but arrived later are not lost. Entries already seen
+    /// with that timestamp are skipped.
+    fn advance(&mut self, entries: Vec<LogEntry>) -> Vec<LogEntry> {
+        let mut seen = self.seen.clone();
+        let mut new = Vec::new();
+        for entry in entries {
+            if self.timestamp.as_ref() == Some(&entry.timestamp) {
+                let pos = seen.iter().position(|msg| msg == &entry.message);
+                if let Some(pos) = pos {
+                    seen.swap_remove(pos);
+                    continue;
+                }
+            }
+            new.push(entry);
+        }
+        if let Some(last) = new.last() {
+            if self.timestamp.as_ref() != Some(&last.timestamp) {
+                self.timestamp = Some(last.timestamp.clone());
+                self.seen.clear();
+            }
+            self.seen.extend(
+                new.iter()
+                    .filter(|entry| entry.timestamp == last.timestamp)
+                    .map(|entry| entry.message.clone()),
+            );
+        }
+        new
+    }
+}
+
+fn print_log_entries(entries: &[LogEntry]) {
+    for entry in entries {
+        println!("{} {}", entry.timestamp, entry.message);
+    }
+}
+
+/// Splits `ORG/NAME` into organization and instance name, validating both
+fn parse_org_instance(name: &str) -> anyhow::Result<(Option<&str>, &str)> {
+    let (org, name) = match name.split_once('/') {
+        Some((org, name)) => (Some(org), name),
+        None => (None, name),
+    };
+    if let Some(org) = org {
+        let valid = !org.is_empty()
+            && org
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            anyhow::bail!(
+                "Invalid organization name {:?}, expected ORG/NAME",
+                org
+            );
+        }
+    }
+    if !is_valid_name(name) {
+        anyhow::bail!(
+            "Instance name must be a valid identifier, \
+             (regex: ^[a-zA-Z_][a-zA-Z_0-9]*$), got {:?}",
+            name
+        );
+    }
+    Ok((org, name))
+}
+
+/// Splits `ORG/NAME` into organization and instance name
+///
+/// If the organization is omitted, it's looked up by the instance name.
+async fn resolve_org_instance(
+    name: &str,
+    client: &CloudClient,
+) -> anyhow::Result<(String, String)> {
+    let name = match parse_org_instance(name)? {
+        (Some(org), name) => return Ok((org.into(), name.into())),
+        (None, name) => name,
+    };
+    let instance = get_cloud_instance_by_name(name, client).await?;
+    let org = instance.org_slug.with_context(|| {
+        format!(
+            "Cannot determine organization of instance {}, \
+             use ORG/{} instead",
+            name, name
+        )
+    })?;
+    Ok((org, instance.name))
+}
+
+pub async fn logs(
+    cmd: &cloud_options::Logs,
+    options: &CloudOptions,
+) -> anyhow::Result<()> {
+    let client = auth::authenticated_client(options).await?;
+    let (org, name) = resolve_org_instance(&cmd.name, &client).await?;
+    let base = format!(
+        "orgs/{}/instances/{}/logs",
+        urlencoding::encode(&org),
+        urlencoding::encode(&name)
+    );
+    let uri = match cmd.tail {
+        Some(tail) => format!("{}?tail={}", base, tail),
+        None => base.clone(),
+    };
+    let mut cursor = LogCursor::default();
+    let entries: Vec<LogEntry> = client.get(uri).await?;
+    print_log_entries(&cursor.advance(entries));
+    if !cmd.follow {
+        return Ok(());
+    }
+    let interval = polling_interval();
+    let mut failures = 0;
+    loop {
+        task::sleep(interval).await;
+        let uri = match &cursor.timestamp {
+            Some(timestamp) => {
+                format!("{}?since={}", base, urlencoding::encode(timestamp))
+            }
+            None => base.clone(),
+        };
+        match client.get::<Vec<LogEntry>>(uri).await {
+            Ok(entries) => {
+                failures = 0;
+                print_log_entries(&cursor.advance(entries));
+            }
+            Err(e) if failures < MAX_POLL_FAILURES
+                && !e.is::<NotAuthenticated>()
+            => {
+                failures += 1;
+                log::warn!("Cannot fetch logs of instance {}/{}: {:#}",
+                           org, name, e);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Returns server versions supported by EdgeDB Cloud
+///
+/// If `instance` is specified, only versions it can be upgraded to are
+/// returned. Upgrade targets should be validated against this list.
+pub async fn available_versions(
+    client: &CloudClient,
+    instance: Option<&CloudInstance>,
+) -> anyhow::Result<Vec<CloudVersion>> {
+    if let Some(instance) = instance {
+        client
+            .get(format!("instances/{}/versions/", instance.id))
+            .await
+    } else {
+        client.get("versions/").await
+    }
+}
+
+pub async fn list_orgs(
+    cmd: &cloud_options::OrgsList,
+    options: &CloudOptions,
+) -> anyhow::Result<()> {
+    let client = auth::authenticated_client(options).await?;
+    let mut orgs: Vec<Org> = client.get_all("orgs/").await?;
+    orgs.sort_by(|a, b| a.name.cmp(&b.name));
+    if cmd.json {
+        println!("{}", serde_json::to_string_pretty(&orgs)?);
+        return Ok(());
+    }
+    if orgs.is_empty() {
+        print::warn("No organizations found");
+        return Ok(());
+    }
+    let mut table = Table::new();
+    table.set_format(*table::FORMAT);
+    table.set_titles(Row::new(
+        ["Slug", "Name", "ID"]
+            .iter()
+            .map(|x| table::header_cell(x))
+            .collect(),
+    ));
+    for org in &orgs {
+        table.add_row(Row::new(vec![
+            Cell::new(org.slug.as_deref().unwrap_or("-")),
+            Cell::new(&org.name),
+            Cell::new(&org.id),
+        ]));
+    }
+    table.printstd();
+    Ok(())
+}
+
+pub async fn versions(
+    cmd: &cloud_options::Versions,
+    options: &CloudOptions,
+) -> anyhow::Result<()> {
+    let client = auth::authenticated_client(options).await?;
+    let instance = if let Some(name) = &cmd.name {
+        Some(get_cloud_instance_by_name(name, &client).await?)
+    } else {
+        None
+    };
+    let versions = available_versions(&client, instance.as_ref()).await?;
+    if cmd.json {
+        println!("{}", serde_json::to_string_pretty(&versions)?);
+        return Ok(());
+    }
+    if versions.is_empty() {
+        if let Some(instance) = &instance {
+            print::warn(format!(
+                "No upgrades are available for instance {}.",
+                instance.name
+            ));
+        } else {
+            print::warn("No versions found");
+        }
+        return Ok(());
+    }
+    let mut table = Table::new();
+    table.set_format(*table::FORMAT);
+    table.set_titles(Row::new(vec![table::header_cell("Version")]));
+    for version in &versions {
+        table.add_row(Row::new(vec![Cell::new(&version.version)]));
+    }
+    table.printstd();
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+
+    use test_case::test_case;
+    use super::{instance_ready, instance_updated, CloudInstance, Summary};
+    use super::{CloudInstanceResize, LogCursor, LogEntry};
+    use super::{in_org, parse_org_instance, Org};
+
+    fn instance(dsn: &str, status: &str) -> CloudInstance {
+        CloudInstance {
+            id: "id".into(),
+            name: "inst".into(),
+            dsn: dsn.into(),
+            status: status.into(),
+            tls_ca: None,
+            org_id: None,
+            org_slug: None,
+            region: None,
+            tier: None,
+            version: None,
+        }
+    }
+
+    #[test_case("", "creating", false => false)]
+    #[test_case("edgedb://host", "creating", false => false)]
+    #[test_case("edgedb://host", "creating", true => true)]
+    #[test_case("", "available", false => false)]
+    #[test_case("edgedb://host", "available", false => true)]
+    #[test_case("edgedb://host", "available", true => true)]
+    fn ready(dsn: &str, status: &str, early_dsn: bool) -> bool {
+        instance_ready(&instance(dsn, status), early_dsn).unwrap()
+    }
+
+    #[test]
+    fn failed() {
+        assert!(instance_ready(&instance("", "failed"), false).is_err());
+        assert!(instance_ready(&instance("edgedb://host", "failed"), false)
+                .is_err());
+    }
+
+    fn resize_tier(tier: &str) -> CloudInstanceResize {
+        CloudInstanceResize { version: None, tier: Some(tier.into()) }
+    }
+
+    #[test_case("available", Some("free") => false)]
+    #[test_case("available", Some("pro") => true)]
+    #[test_case("updating", Some("pro") => false)]
+    fn updated(status: &str, tier: Option<&str>) -> bool {
+        let mut inst = instance("edgedb://host", status);
+        inst.tier = tier.map(String::from);
+        instance_updated(&inst, &resize_tier("pro"), &Cell::new(false))
+            .unwrap()
+    }
+
+    #[test]
+    fn updated_after_transition() {
+        let request = resize_tier("pro");
+        let transitioned = Cell::new(false);
+        let mut inst = instance("edgedb://host", "available");
+        // old settings are still reported right after the request
+        assert!(!instance_updated(&inst, &request, &transitioned).unwrap());
+        inst.status = "updating".into();
+        assert!(!instance_updated(&inst, &request, &transitioned).unwrap());
+        // API might not report the tier at all
+        inst.status = "available".into();
+        assert!(instance_updated(&inst, &request, &transitioned).unwrap());
+    }
+
+    #[test]
+    fn update_failed() {
+        assert!(instance_updated(&instance("edgedb://host", "failed"),
+                                 &resize_tier("pro"), &Cell::new(false))
+                .is_err());
+    }
+
+    #[test]
+    fn summary() {
+        let mut instances = vec![
+            instance("edgedb://host", "available"),
+            instance("edgedb://host", "available"),
+            instance("", "creating"),
+        ];
+        instances[0].org_slug = Some("acme".into());
+        instances[2].org_slug = Some("acme".into());
+        let summary = Summary::new(instances.iter());
+        assert_eq!(summary.total, 3);
+        assert_eq!(
+            summary.by_status.into_iter().collect::<Vec<_>>(),
+            vec![("available", 2), ("creating", 1)]
+        );
+        assert_eq!(
+            summary.by_org.into_iter().collect::<Vec<_>>(),
+            vec![("-", 1), ("acme", 2)]
+        );
+    }
+
+    fn log(timestamp: &str, message: &str) -> LogEntry {
+        LogEntry {
+            timestamp: timestamp.into(),
+            message: message.into(),
+        }
+    }
+
+    fn messages(entries: Vec<LogEntry>) -> Vec<String> {
+        entries.into_iter().map(|entry| entry.message).collect()
+    }
+
+    #[test]
+    fn log_cursor() {
+        let mut cursor = LogCursor::default();
+        let new = cursor.advance(vec![log("1", "a"), log("2", "b")]);
+        assert_eq!(messages(new), ["a", "b"]);
+        // `since` is inclusive, entry "c" arrived after the first poll
+        let new = cursor.advance(vec![log("2", "b"), log("2", "c")]);
+        assert_eq!(messages(new), ["c"]);
+        let new = cursor.advance(vec![log("2", "b"), log("2", "c")]);
+        assert!(new.is_empty());
+        // repeated message with the same timestamp is a new entry
+        let new = cursor.advance(vec![
+            log("2", "b"), log("2", "c"), log("2", "c"), log("3", "d"),
+        ]);
+        assert_eq!(messages(new), ["c", "d"]);
+        assert_eq!(cursor.timestamp.as_deref(), Some("3"));
+        assert_eq!(cursor.seen, ["d"]);
+    }
+
+    fn org(id: &str, slug: Option<&str>) -> Org {
+        Org {
+            id: id.into(),
+            name: "Acme".into(),
+            slug: slug.map(|slug| slug.into()),
+        }
+    }
+
+    #[test_case(Some("org1"), None, org("org1", None) => true)]
+    #[test_case(Some("org2"), None, org("org1", Some("acme")) => false)]
+    #[test_case(None, Some("acme"), org("org1", Some("acme")) => true)]
+    #[test_case(None, Some("other"), org("org1", Some("acme")) => false)]
+    #[test_case(None, None, org("org1", None) => false)]
+    fn instance_in_org(org_id: Option<&str>, org_slug: Option<&str>, org: Org)
+        -> bool
+    {
+        let mut inst = instance("edgedb://host", "available");
+        inst.org_id = org_id.map(|id| id.into());
+        inst.org_slug = org_slug.map(|slug| slug.into());
+        in_org(&inst, &org)
+    }
+
+    #[test_case("inst" => Some((None, "inst".into())))]
+    #[test_case("acme/inst" => Some((Some("acme".into()), "inst".into())))]
+    #[test_case("my-org/inst_2"
+                => Some((Some("my-org".into()), "inst_2".into())))]
+    #[test_case("acme/inst/extra" => None)]
+    #[test_case("/inst" => None)]
+    #[test_case("acme/" => None)]
+    #[test_case("acme /inst" => None)]
+    #[test_case("acme/ inst" => None)]
+    fn org_instance(name: &str) -> Option<(Option<String>, String)> {
+        parse_org_instance(name)
+            .ok()
+            .map(|(org, name)| (org.map(String::from), name.into()))
+    }
+}
@@ -1,5 +1,8 @@
+use std::path::PathBuf;
+
 use edgedb_cli_derive::{EdbClap};
 
+use crate::cloud::maintenance;
 use crate::options::CloudOptions;
 
 
@@ -15,7 +18,95 @@ pub enum Command {
     #[edb(inherit(CloudOptions))]
     Login(Login),
     /// Forget the stored access token
+    #[edb(inherit(CloudOptions))]
     Logout(Logout),
+    /// Manage EdgeDB Cloud instances
+    Instance(CloudInstanceCommand),
+    /// Manage EdgeDB Cloud organizations
+    Orgs(CloudOrgsCommand),
+}
+
+#[derive(EdbClap, Debug, Clone)]
+pub struct CloudOrgsCommand {
+    #[clap(subcommand)]
+    pub subcommand: OrgsCommand,
+}
+
+#[derive(EdbClap, Clone, Debug)]
+pub enum OrgsCommand {
+    /// List organizations available to the current user
+    #[edb(inherit(CloudOptions))]
+    List(OrgsList),
+}
+
+#[derive(EdbClap, Debug, Clone)]
+pub struct CloudInstanceCommand {
+    #[clap(subcommand)]
+    pub subcommand: InstanceCommand,
+}
+
+#[derive(EdbClap, Clone, Debug)]
+pub enum InstanceCommand {
+    /// Show status of an EdgeDB Cloud instance
+    #[edb(inherit(CloudOptions))]
+    Status(Status),
+    /// Change version or tier of an EdgeDB Cloud instance
+    #[edb(inherit(CloudOptions))]
+    Resize(Resize),
+    /// Write credentials of an EdgeDB Cloud instance to a file
+    #[edb(inherit(CloudOptions))]
+    Export(Export),
+    /// Update local credentials of a linked EdgeDB Cloud instance
+    #[edb(inherit(CloudOptions))]
+    Refresh(Refresh),
+    /// Show resource usage of an EdgeDB Cloud instance
+    #[edb(inherit(CloudOptions))]
+    Metrics(Metrics),
+    /// Show logs of an EdgeDB Cloud instance
+    #[edb(inherit(CloudOptions))]
+    Logs(Logs),
+    /// Manage secrets of an EdgeDB Cloud instance
+    Secret(InstanceSecretCommand),
+    /// List server versions supported by EdgeDB Cloud
+    #[edb(inherit(CloudOptions))]
+    Versions(Versions),
+    /// Show or change when disruptive maintenance may happen
+    MaintenanceWindow(InstanceMaintenanceCommand),
+}
+
+#[derive(EdbClap, Debug, Clone)]
+pub struct InstanceMaintenanceCommand {
+    #[clap(subcommand)]
+    pub subcommand: MaintenanceCommand,
+}
+
+#[derive(EdbClap, Clone, Debug)]
+pub enum MaintenanceCommand {
+    /// Show maintenance window of the instance
+    #[edb(inherit(CloudOptions))]
+    Show(MaintenanceShow),
+    /// Set maintenance window of the instance
+    #[edb(inherit(CloudOptions))]
+    Set(MaintenanceSet),
+}
+
+#[derive(EdbClap, Debug, Clone)]
+pub struct InstanceSecretCommand {
+    #[clap(subcommand)]
+    pub subcommand: SecretCommand,
+}
+
+#[derive(EdbClap, Clone, Debug)]
+pub enum SecretCommand {
+    /// Set a secret (value is read from the terminal by default)
+    #[edb(inherit(CloudOptions))]
+    Set(SecretSet),
+    /// List names of the secrets (values are never shown)
+    #[edb(inherit(CloudOptions))]
+    List(SecretList),
+    /// Remove a secret
+    #[edb(inherit(CloudOptions))]
+    Unset(SecretUnset),
 }
 
 #[derive(EdbClap, Debug, Clone)]
@@ -26,3 +117,160 @@ pub struct Login {
 pub struct Logout {
 
 }
+
+#[derive(EdbClap, Debug, Clone)]
+pub struct Logs {
+    /// Name of the EdgeDB Cloud instance, as `ORG/NAME` or just `NAME`
+    pub name: String,
+
+    /// Number of lines to show
+    #[clap(short='n', long)]
+    pub tail: Option<usize>,
+
+    /// Show log's tail and then continue watching for the new entries
+    #[clap(short='f', long)]
+    pub follow: bool,
+}
+
+#[derive(EdbClap, Debug, Clone)]
+pub struct Metrics {
+    /// Name of the EdgeDB Cloud instance
+    pub name: String,
+
+    /// Output in JSON format
+    #[clap(long)]
+    pub json: bool,
+}
+
+#[derive(EdbClap, Debug, Clone)]
+pub struct Status {
+    /// Name of the EdgeDB Cloud instance
+    pub name: String,
+
+    /// Output in JSON format
+    #[clap(long)]
+    pub json: bool,
+}
+
+#[derive(EdbClap, Debug, Clone)]
+pub struct Resize {
+    /// Name of the EdgeDB Cloud instance
+    pub name: String,
+
+    /// Server version to upgrade to (see `edgedb cloud instance versions`)
+    #[clap(long, required_unless_present="tier")]
+    pub version: Option<String>,
+    /// Instance tier
+    #[clap(long)]
+    pub tier: Option<String>,
+
+    /// Seconds to wait for the instance to become available again
+    /// (default is 300, or `EDGEDB_CLOUD_CREATE_TIMEOUT`)
+    #[clap(long, hide=true)]
+    pub timeout: Option<u64>,
+}
+
+#[derive(EdbClap, Debug, Clone)]
+pub struct Export {
+    /// Name of the EdgeDB Cloud instance
+    pub name: String,
+
+    /// Path to write credentials file to
+    #[clap(short='o', long, required_unless_present="json")]
+    pub output: Option<PathBuf>,
+    /// Print credentials to stdout instead of writing a file
+    #[clap(long, conflicts_with="output")]
+    pub json: bool,
+}
+
+#[derive(EdbClap, Debug, Clone)]
+pub struct Refresh {
+    /// Name of the EdgeDB Cloud instance
+    pub name: String,
+}
+
+#[derive(EdbClap, Debug, Clone)]
+pub struct OrgsList {
+    /// Output in JSON format
+    #[clap(long)]
+    pub json: bool,
+}
+
+#[derive(EdbClap, Debug, Clone)]
+pub struct Versions {
+    /// Show only versions the given instance can be upgraded to
+    pub name: Option<String>,
+
+    /// Output in JSON format
+    #[clap(long)]
+    pub json: bool,
+}
+
+#[derive(EdbClap, Debug, Clone)]
+pub struct MaintenanceShow {
+    /// Name of the EdgeDB Cloud instance
+    pub name: String,
+
+    /// Output in JSON format
+    #[clap(long)]
+    pub json: bool,
+}
+
+#[derive(EdbClap, Debug, Clone)]
+pub struct MaintenanceSet {
+    /// Name of the EdgeDB Cloud instance
+    pub name: String,
+
+    /// Day of the week
+    #[clap(long, possible_values=&[
+        "mon", "tue", "wed", "thu", "fri", "sat", "sun",
+    ])]
+    pub day: String,
+    /// Start time of the window in `HH:MM` format
+    #[clap(long, parse(try_from_str=maintenance::parse_time))]
+    pub time: String,
+    /// Timezone: `UTC` or an offset like `UTC+05:30`
+    #[clap(long, default_value="UTC",
+           parse(try_from_str=maintenance::parse_timezone))]
+    pub timezone: String,
+}
+
+#[derive(EdbClap, Debug, Clone)]
+pub struct SecretSet {
+    /// Name of the EdgeDB Cloud instance
+    pub name: String,
+    /// Name of the secret
+    pub key: String,
+
+    /// Secret value (note: it may be saved in the shell history)
+    #[clap(long, conflicts_with_all=&["value_file", "value_from_stdin"])]
+    pub value: Option<String>,
+    /// Read secret value from a file
+    #[clap(long, conflicts_with="value_from_stdin")]
+    pub value_file: Option<PathBuf>,
+    /// Read secret value from the first line of stdin
+    #[clap(long)]
+    pub value_from_stdin: bool,
+}
+
+#[derive(EdbClap, Debug, Clone)]
+pub struct SecretList {
+    /// Name of the EdgeDB Cloud instance
+    pub name: String,
+
+    /// Output in JSON format
+    #[clap(long)]
+    pub json: bool,
+}
+
+#[derive(EdbClap, Debug, Clone)]
+pub struct SecretUnset {
+    /// Name of the EdgeDB Cloud instance
+    pub name: String,
+    /// Name of the secret
+    pub key: String,
+
+    /// Do not ask for confirmation
+    #[clap(long)]
+    pub force: bool,
+}
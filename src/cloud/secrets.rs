@@ -0,0 +1,102 @@
+use std::fs;
+
+use anyhow::Context;
+
+use crate::cloud::auth;
+use crate::cloud::ops::get_cloud_instance_by_name;
+use crate::cloud::options::{SecretList, SecretSet, SecretUnset};
+use crate::options::CloudOptions;
+use crate::print::{self, echo, Highlight};
+use crate::question;
+use crate::table::{self, Cell, Row, Table};
+use crate::tty_password;
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct Secret {
+    name: String,
+}
+
+fn read_value(cmd: &SecretSet) -> anyhow::Result<String> {
+    if let Some(value) = &cmd.value {
+        Ok(value.clone())
+    } else if let Some(path) = &cmd.value_file {
+        let value = fs::read_to_string(path)
+            .with_context(|| format!("cannot read {:?}", path))?;
+        // files usually end with a newline which is not a part of the value
+        Ok(value.strip_suffix('\n').unwrap_or(&value).into())
+    } else if cmd.value_from_stdin {
+        tty_password::read_stdin()
+    } else if atty::is(atty::Stream::Stdin) {
+        tty_password::read(format!("Value for {}: ", cmd.key.escape_default()))
+    } else {
+        anyhow::bail!(
+            "Stdin is not a terminal, \
+             use `--value-from-stdin` or `--value-file` to pass the value"
+        );
+    }
+}
+
+pub async fn set(cmd: &SecretSet, options: &CloudOptions) -> anyhow::Result<()> {
+    let value = read_value(cmd)?;
+    let client = auth::authenticated_client(options).await?;
+    let instance = get_cloud_instance_by_name(&cmd.name, &client).await?;
+    let _: serde_json::Value = client
+        .put(
+            format!(
+                "instances/{}/secrets/{}",
+                instance.id,
+                urlencoding::encode(&cmd.key)
+            ),
+            serde_json::json!({ "value": value }),
+        )
+        .await?;
+    print::success(format!("Secret {} is set.", cmd.key));
+    Ok(())
+}
+
+pub async fn list(cmd: &SecretList, options: &CloudOptions) -> anyhow::Result<()> {
+    let client = auth::authenticated_client(options).await?;
+    let instance = get_cloud_instance_by_name(&cmd.name, &client).await?;
+    let mut secrets: Vec<Secret> = client
+        .get(format!("instances/{}/secrets/", instance.id))
+        .await?;
+    secrets.sort_by(|a, b| a.name.cmp(&b.name));
+    if cmd.json {
+        println!("{}", serde_json::to_string_pretty(&secrets)?);
+    } else if secrets.is_empty() {
+        print::warn("No secrets found");
+    } else {
+        let mut table = Table::new();
+        table.set_format(*table::FORMAT);
+        table.set_titles(Row::new(vec![table::header_cell("Name")]));
+        for secret in &secrets {
+            table.add_row(Row::new(vec![Cell::new(&secret.name)]));
+        }
+        table.printstd();
+    }
+    Ok(())
+}
+
+pub async fn unset(cmd: &SecretUnset, options: &CloudOptions) -> anyhow::Result<()> {
+    let client = auth::authenticated_client(options).await?;
+    let instance = get_cloud_instance_by_name(&cmd.name, &client).await?;
+    if !cmd.force {
+        let q = question::Confirm::new_dangerous(format!(
+            "Do you really want to remove secret {:?} of instance {:?}?",
+            cmd.key, instance.name
+        ));
+        if !q.ask()? {
+            print::error("Canceled.");
+            return Ok(());
+        }
+    }
+    let _: serde_json::Value = client
+        .delete(format!(
+            "instances/{}/secrets/{}",
+            instance.id,
+            urlencoding::encode(&cmd.key)
+        ))
+        .await?;
+    echo!("Secret", cmd.key.emphasize(), "is removed.");
+    Ok(())
+}
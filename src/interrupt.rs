@@ -224,6 +224,10 @@ impl Interrupt {
     pub async fn wait(&self) -> Signal {
         self.event.wait().await
     }
+    /// Returns whether a signal was received, without resetting it
+    pub fn is_set(&self) -> bool {
+        self.event.first.load().is_some()
+    }
     pub fn err_if_occurred(&self) -> anyhow::Result<()> {
         if let Some(sig) = self.event.first.load() {
             self.event.clear();
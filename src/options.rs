@@ -202,6 +202,12 @@ pub struct CloudOptions {
     #[clap(long, name="URL", help_heading=Some(CLOUD_OPTIONS_GROUP))]
     #[clap(hide=true)]
     pub cloud_access_token: Option<String>,
+
+    /// Specify the name of the EdgeDB Cloud profile to use. Every profile
+    /// remembers its own access token.
+    #[clap(long, name="PROFILE", help_heading=Some(CLOUD_OPTIONS_GROUP))]
+    #[clap(hide=true, validator(cloud_profile_opt))]
+    pub cloud_profile: Option<String>,
 }
 
 /// Use the `edgedb` command-line tool to spin up local instances,
@@ -345,6 +351,14 @@ fn parse_duration(value: &str) -> anyhow::Result<Duration> {
     }
 }
 
+fn cloud_profile_opt(name: &str) -> Result<(), String> {
+    if portable::local::is_valid_name(name) {
+        return Ok(())
+    }
+    return Err("profile name must be a valid identifier, \
+                (regex: ^[a-zA-Z_][a-zA-Z_0-9]*$)".into())
+}
+
 fn say_option_is_deprecated(option_name: &str, suggestion: &str) {
     let mut error = "warning:".to_string();
     let mut instead = suggestion.to_string();
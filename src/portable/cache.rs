@@ -0,0 +1,572 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use anyhow::Context;
+use fs_err as fs;
+use indicatif::{HumanBytes, ProgressBar, ProgressStyle};
+
+use crate::platform::cache_dir;
+use crate::portable::hash::{self, HashKind};
+use crate::portable::options::{ListCache, VerifyCache, CachePath};
+use crate::portable::options::CheckPackage;
+use crate::portable::repository::{get_server_package, Channel, PackageInfo};
+use crate::portable::repository::{PackageHash, Query};
+use crate::portable::repository::decode_file_component;
+use crate::portable::ver;
+use crate::print::{self, echo};
+use crate::table::{self, Table, Row, Cell};
+
+
+#[derive(Debug)]
+pub struct CacheEntry {
+    pub path: PathBuf,
+    pub version: ver::Build,
+    pub hash: String,
+    pub size: u64,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all="kebab-case")]
+struct JsonCachePath<'a> {
+    path: &'a Path,
+    exists: bool,
+    verified: bool,
+    package: &'a PackageInfo,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all="kebab-case")]
+struct JsonCheckPackage<'a> {
+    path: &'a Path,
+    size: u64,
+    size_matches: Option<bool>,
+    /// `None` if hash has unknown format and can't be verified
+    hash_matches: Option<bool>,
+    package: &'a PackageInfo,
+}
+
+/// Result of comparing a local file with the package in the repository
+#[derive(Debug, PartialEq, Eq)]
+struct PackageCheck {
+    size: u64,
+    /// `None` if size of the package is unknown
+    size_matches: Option<bool>,
+    /// `None` if hash of the package has unknown format
+    hash_matches: Option<bool>,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all="kebab-case")]
+struct JsonCacheEntry<'a> {
+    path: &'a Path,
+    version: String,
+    channel: Option<Channel>,
+    size: u64,
+    hash: &'a str,
+    /// `None` unless `--verify` is passed, or if the file name has no hash
+    /// to verify against
+    verified: Option<bool>,
+}
+
+#[derive(Debug)]
+enum Verification {
+    Ok,
+    Mismatch(String),
+    /// Package was installed with `--no-verify`, so there is no hash
+    Unverifiable,
+    Error(anyhow::Error),
+}
+
+pub fn download_dir() -> anyhow::Result<PathBuf> {
+    Ok(cache_dir()?.join("downloads"))
+}
+
+/// Parses file name produced by `PackageInfo::cache_file_name`
+pub fn parse_cache_file_name(name: &str) -> Option<(ver::Build, String)> {
+    let name = name.strip_prefix("edgedb-server_")?;
+    let name = name.strip_suffix(".tar.zst")
+        .or_else(|| name.strip_suffix(".zip"))?;
+    let (version, hash) = name.rsplit_once('_')?;
+    let version = decode_file_component(version)?;
+    Some((version.parse().ok()?, decode_file_component(hash)?))
+}
+
+pub fn list_entries() -> anyhow::Result<Vec<CacheEntry>> {
+    let dir = download_dir()?;
+    let dir_entries = match fs::read_dir(&dir) {
+        Ok(d) => d,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e)?,
+    };
+    let mut result = Vec::new();
+    for item in dir_entries {
+        let item = item?;
+        let file_name = item.file_name();
+        let parsed = file_name.to_str().and_then(parse_cache_file_name);
+        if let Some((version, hash)) = parsed {
+            result.push(CacheEntry {
+                path: item.path(),
+                version,
+                hash,
+                size: item.metadata()?.len(),
+            });
+        } else {
+            log::debug!("Skipping unknown file {:?}", item.path());
+        }
+    }
+    result.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(result)
+}
+
+/// Returns hex digests of the file by every supported algorithm,
+/// blake2b first
+fn hash_file(path: &Path, bar: &ProgressBar) -> anyhow::Result<Vec<String>> {
+    let file = fs::File::open(path)?;
+    let kinds = [HashKind::Blake2b, HashKind::Blake3, HashKind::Sha256];
+    let digests = hash::hash_reader_multi(file, &kinds,
+                                          |bytes| bar.inc(bytes))?;
+    Ok(digests.iter().map(hex::encode).collect())
+}
+
+fn verify_entry(entry: &CacheEntry, bar: &ProgressBar) -> Verification {
+    if entry.hash.is_empty() ||
+        !entry.hash.chars().all(|c| c.is_ascii_hexdigit())
+    {
+        bar.inc(entry.size);
+        return Verification::Unverifiable;
+    }
+    // file name doesn't record which algorithm the hash is computed by
+    match hash_file(&entry.path, bar) {
+        Ok(hexes) if hexes.iter().any(|hex| hex.starts_with(&entry.hash)) => {
+            Verification::Ok
+        }
+        Ok(mut hexes) => Verification::Mismatch(hexes.remove(0)),
+        Err(e) => Verification::Error(e),
+    }
+}
+
+fn verify_all(entries: &[CacheEntry], jobs: usize)
+    -> anyhow::Result<Vec<Verification>>
+{
+    let bar = ProgressBar::new(entries.iter().map(|e| e.size).sum());
+    bar.set_style(
+        ProgressStyle::default_bar()
+        .template("Verifying [{bar}] {bytes:>7.dim}/{total_bytes:7}")
+        .progress_chars("=> "));
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(
+        entries.iter().map(|_| None).collect::<Vec<_>>());
+    crossbeam_utils::thread::scope(|scope| {
+        for _ in 0..jobs.max(1).min(entries.len()) {
+            scope.spawn(|_| loop {
+                let idx = next.fetch_add(1, Ordering::SeqCst);
+                let entry = match entries.get(idx) {
+                    Some(entry) => entry,
+                    None => break,
+                };
+                let result = verify_entry(entry, &bar);
+                results.lock().unwrap()[idx] = Some(result);
+            });
+        }
+    }).map_err(|_| anyhow::anyhow!("verification thread panicked"))?;
+    bar.finish_and_clear();
+    Ok(results.into_inner().unwrap().into_iter()
+        .map(|r| r.expect("all entries are verified"))
+        .collect())
+}
+
+pub fn verify_cache(options: &VerifyCache) -> anyhow::Result<()> {
+    let entries = list_entries()?;
+    if entries.is_empty() {
+        print::warn("No cached packages found");
+        return Ok(());
+    }
+    let jobs = options.jobs.unwrap_or_else(num_cpus::get);
+    let results = verify_all(&entries, jobs)
+        .context("cannot verify cached packages")?;
+
+    let mut failed = 0;
+    let mut unverifiable = 0;
+    let mut table = Table::new();
+    table.set_format(*table::FORMAT);
+    table.set_titles(Row::new(
+        ["Version", "Path", "Status"]
+        .iter().map(|x| table::header_cell(x)).collect()));
+    for (entry, result) in entries.iter().zip(&results) {
+        let status = match result {
+            Verification::Ok => "ok".into(),
+            Verification::Mismatch(hash) => {
+                failed += 1;
+                format!("hash mismatch: {:.7}", hash)
+            }
+            Verification::Unverifiable => {
+                unverifiable += 1;
+                "cannot verify (no hash)".into()
+            }
+            Verification::Error(e) => {
+                failed += 1;
+                format!("error: {:#}", e)
+            }
+        };
+        table.add_row(Row::new(vec![
+            Cell::new(&entry.version.to_string()),
+            Cell::new(&entry.path.display().to_string()),
+            Cell::new(&status),
+        ]));
+    }
+    table.printstd();
+    if failed > 0 {
+        anyhow::bail!("{} of {} cached packages failed verification",
+                      failed, entries.len());
+    }
+    if unverifiable > 0 {
+        echo!(entries.len() - unverifiable, "cached packages are valid,",
+              unverifiable, "cannot be verified.");
+    } else {
+        echo!("All", entries.len(), "cached packages are valid.");
+    }
+    Ok(())
+}
+
+pub fn list_cache(options: &ListCache) -> anyhow::Result<()> {
+    let entries = list_entries()?;
+    let results = if options.verify && !entries.is_empty() {
+        let jobs = options.jobs.unwrap_or_else(num_cpus::get);
+        Some(verify_all(&entries, jobs)
+             .context("cannot verify cached packages")?)
+    } else {
+        None
+    };
+    let verified = |idx: usize| {
+        match results.as_ref()?[idx] {
+            Verification::Ok => Some(true),
+            Verification::Unverifiable => None,
+            _ => Some(false),
+        }
+    };
+    if options.json {
+        let items = entries.iter().enumerate().map(|(idx, entry)| {
+            JsonCacheEntry {
+                path: &entry.path,
+                version: entry.version.to_string(),
+                channel: Channel::from_version(&entry.version.specific()).ok(),
+                size: entry.size,
+                hash: &entry.hash,
+                verified: verified(idx),
+            }
+        }).collect::<Vec<_>>();
+        println!("{}", serde_json::to_string_pretty(&items)?);
+        return Ok(());
+    }
+    if entries.is_empty() {
+        print::warn("No cached packages found");
+        return Ok(());
+    }
+    let mut table = Table::new();
+    table.set_format(*table::FORMAT);
+    let mut titles = vec!["Version", "Size", "Path"];
+    if results.is_some() {
+        titles.push("Verified");
+    }
+    table.set_titles(Row::new(
+        titles.iter().map(|x| table::header_cell(x)).collect()));
+    for (idx, entry) in entries.iter().enumerate() {
+        let mut row = vec![
+            Cell::new(&entry.version.to_string()),
+            Cell::new(&HumanBytes(entry.size).to_string()),
+            Cell::new(&entry.path.display().to_string()),
+        ];
+        if results.is_some() {
+            row.push(Cell::new(match verified(idx) {
+                Some(true) => "yes",
+                Some(false) => "NO",
+                None => "unknown",
+            }));
+        }
+        table.add_row(Row::new(row));
+    }
+    table.printstd();
+    Ok(())
+}
+
+pub fn verify_package(path: &Path, pkg_info: &PackageInfo) -> anyhow::Result<bool> {
+    if let PackageHash::Unknown(val) = &pkg_info.hash {
+        log::warn!("Cannot verify hash, unknown hash format {:?}", val);
+        return Ok(false);
+    }
+    let digest = hash::hash_file(path, pkg_info.hash.kind())?;
+    Ok(pkg_info.hash.verify_digest(&digest))
+}
+
+pub fn cache_path(options: &CachePath) -> anyhow::Result<()> {
+    let query = Query::from_options(options.nightly, &options.version)?;
+    let pkg_info = get_server_package(&query)?
+        .context("no package matching your criteria found")?;
+    let path = download_dir()?.join(pkg_info.cache_file_name());
+    let exists = path.exists();
+    let verified = exists && verify_package(&path, &pkg_info)?;
+    if options.json {
+        println!("{}", serde_json::to_string_pretty(&JsonCachePath {
+            path: &path,
+            exists,
+            verified,
+            package: &pkg_info,
+        })?);
+    } else {
+        table::settings(&[
+            ("Version", &pkg_info.version.to_string()),
+            ("Path", &path.display().to_string()),
+            ("Status", if verified {
+                "cached and verified"
+            } else if exists {
+                "cached, hash mismatch"
+            } else {
+                "not downloaded"
+            }),
+        ]);
+    }
+    Ok(())
+}
+
+fn check_file(path: &Path, pkg_info: &PackageInfo)
+    -> anyhow::Result<PackageCheck>
+{
+    let size = fs::metadata(path)?.len();
+    let hash_matches = match &pkg_info.hash {
+        PackageHash::Unknown(_) => None,
+        hash => {
+            let digest = hash::hash_file(path, hash.kind())?;
+            Some(hash.verify_digest(&digest))
+        }
+    };
+    Ok(PackageCheck {
+        size,
+        // size is unknown for some older indexes
+        size_matches: pkg_info.expected_size().map(|exp| exp == size),
+        hash_matches,
+    })
+}
+
+pub fn check_package(options: &CheckPackage) -> anyhow::Result<()> {
+    let query = Query::from_options(options.nightly, &options.version)?;
+    let pkg_info = get_server_package(&query)?
+        .context("no package matching your criteria found")?;
+    let check = check_file(&options.path, &pkg_info)?;
+    if options.json {
+        println!("{}", serde_json::to_string_pretty(&JsonCheckPackage {
+            path: &options.path,
+            size: check.size,
+            size_matches: check.size_matches,
+            hash_matches: check.hash_matches,
+            package: &pkg_info,
+        })?);
+    } else {
+        let size_text = match pkg_info.expected_size() {
+            Some(exp) if exp != check.size => {
+                format!("{} (expected {})",
+                        HumanBytes(check.size), HumanBytes(exp))
+            }
+            _ => HumanBytes(check.size).to_string(),
+        };
+        table::settings(&[
+            ("Version", &pkg_info.version.to_string()),
+            ("Path", &options.path.display().to_string()),
+            ("Size", &size_text),
+            ("Hash", match check.hash_matches {
+                Some(true) => "matches",
+                Some(false) => "MISMATCH",
+                None => "cannot verify (unknown hash format)",
+            }),
+        ]);
+    }
+    if check.hash_matches == Some(false) || check.size_matches == Some(false) {
+        anyhow::bail!("{} does not match package {}",
+                      options.path.display(), pkg_info);
+    }
+    if check.hash_matches.is_none() {
+        anyhow::bail!("cannot verify {}: package {} has hash of unknown \
+                       format {}", options.path.display(), pkg_info,
+                       pkg_info.hash);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use indicatif::ProgressBar;
+    use test_case::test_case;
+
+    use crate::portable::repository::{PackageInfo, PackageHash, PackageType};
+    use crate::portable::repository::{encode_file_component};
+    use crate::portable::repository::{decode_file_component};
+
+    use super::{check_file, parse_cache_file_name, PackageCheck};
+    use super::{verify_entry, CacheEntry, Verification};
+
+    fn package(version: &str) -> PackageInfo {
+        PackageInfo {
+            version: version.parse().unwrap(),
+            url: "https://packages.edgedb.com/archive/x.tar.zst"
+                .parse().unwrap(),
+            size: 0,
+            hash: PackageHash::Blake2b("0123456789abcdef".into()),
+            kind: PackageType::TarZst,
+            signature: None,
+        }
+    }
+
+    #[test_case("1.0+d8ab0c4"
+                => "edgedb-server_1.0%2Bd8ab0c4_0123456789abcdef.tar.zst")]
+    #[test_case("2.0-rc.1+local"
+                => "edgedb-server_2.0-rc.1%2Blocal_0123456789abcdef.tar.zst")]
+    #[test_case("2.0-dev.6543+1234567"
+                => "edgedb-server_2.0-dev.6543%2B1234567_0123456789abcdef.tar.zst")]
+    fn file_name(version: &str) -> String {
+        package(version).cache_file_name()
+    }
+
+    #[test_case("1.0+d8ab0c4")]
+    #[test_case("2.0-rc.1+local")]
+    #[test_case("2.0-dev.6543+1234567")]
+    fn round_trip(version: &str) {
+        let name = package(version).cache_file_name();
+        let (parsed, hash) = parse_cache_file_name(&name).unwrap();
+        assert_eq!(parsed.to_string(), version);
+        assert_eq!(hash, "0123456789abcdef");
+    }
+
+    #[test]
+    fn zip_name() {
+        let mut pkg = package("1.0+d8ab0c4");
+        pkg.kind = PackageType::Zip;
+        let name = pkg.cache_file_name();
+        assert_eq!(name, "edgedb-server_1.0%2Bd8ab0c4_0123456789abcdef.zip");
+        let (parsed, _) = parse_cache_file_name(&name).unwrap();
+        assert_eq!(parsed.to_string(), "1.0+d8ab0c4");
+    }
+
+    #[test]
+    fn long_hash_name() {
+        let mut pkg = package("1.0+d8ab0c4");
+        pkg.hash = PackageHash::Blake2b("AB".repeat(64).into());
+        let (_, hash) = parse_cache_file_name(&pkg.cache_file_name()).unwrap();
+        assert_eq!(hash, "ab".repeat(16));
+        assert_eq!(pkg.legacy_cache_file_name(),
+                   "edgedb-server_1.0%2Bd8ab0c4_ABABABA.tar.zst");
+    }
+
+    #[test_case(blake2b_simd::blake2b(b"data").to_hex().to_string())]
+    #[test_case(blake3::hash(b"data").to_hex().to_string())]
+    #[test_case("3a6eb0790f39ac87c94f3856b2dd2c5d".into())]
+    fn verify_kind(hash: String) {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("package.tar.zst");
+        fs::write(&path, b"data").unwrap();
+        let entry = CacheEntry {
+            path,
+            version: "1.0+d8ab0c4".parse().unwrap(),
+            hash: hash[..32].into(),
+            size: 4,
+        };
+        let bar = ProgressBar::hidden();
+        assert!(matches!(verify_entry(&entry, &bar), Verification::Ok));
+        fs::write(&entry.path, b"datA").unwrap();
+        assert!(matches!(verify_entry(&entry, &bar),
+                         Verification::Mismatch(_)));
+    }
+
+    #[test_case("unverified")]
+    #[test_case("")]
+    fn unverifiable(hash: &str) {
+        let entry = CacheEntry {
+            path: "/nonexistent/package.tar.zst".into(),
+            version: "1.0+d8ab0c4".parse().unwrap(),
+            hash: hash.into(),
+            size: 4,
+        };
+        let bar = ProgressBar::hidden();
+        assert!(matches!(verify_entry(&entry, &bar),
+                         Verification::Unverifiable));
+    }
+
+    #[test]
+    fn legacy_name() {
+        let (ver, hash) = parse_cache_file_name(
+            "edgedb-server_1.0+d8ab0c4_0123456.tar.zst").unwrap();
+        assert_eq!(ver.to_string(), "1.0+d8ab0c4");
+        assert_eq!(hash, "0123456");
+    }
+
+    #[test_case("a+b:c" => "a%2Bb%3Ac")]
+    #[test_case("C:\\dir/file" => "C%3A%5Cdir%2Ffile")]
+    #[test_case("x_y*z?\"<>|" => "x%5Fy%2Az%3F%22%3C%3E%7C")]
+    fn encode(val: &str) -> String {
+        let encoded = encode_file_component(val);
+        assert_eq!(decode_file_component(&encoded).as_deref(), Some(val));
+        encoded
+    }
+
+    #[test_case("%2" => None)]
+    #[test_case("%zz" => None)]
+    #[test_case("%FF" => None)]
+    fn invalid_encoding(val: &str) -> Option<String> {
+        decode_file_component(val)
+    }
+
+    fn check(pkg: &PackageInfo, data: &[u8]) -> PackageCheck {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("package.tar.zst");
+        fs::write(&path, data).unwrap();
+        check_file(&path, pkg).unwrap()
+    }
+
+    #[test]
+    fn check_match() {
+        let mut pkg = package("1.0+d8ab0c4");
+        pkg.size = 4;
+        pkg.hash = PackageHash::Blake2b(
+            blake2b_simd::blake2b(b"data").to_hex().to_string().into());
+        assert_eq!(check(&pkg, b"data"), PackageCheck {
+            size: 4,
+            size_matches: Some(true),
+            hash_matches: Some(true),
+        });
+        assert_eq!(check(&pkg, b"other"), PackageCheck {
+            size: 5,
+            size_matches: Some(false),
+            hash_matches: Some(false),
+        });
+    }
+
+    #[test]
+    fn check_sha256() {
+        let mut pkg = package("1.0+d8ab0c4");
+        pkg.hash = PackageHash::Sha256(
+            "3a6eb0790f39ac87c94f3856b2dd2c5d\
+             110e6811602261a9a923d3bb23adc8b7".into());
+        assert_eq!(check(&pkg, b"data").hash_matches, Some(true));
+        assert_eq!(check(&pkg, b"datA").hash_matches, Some(false));
+    }
+
+    #[test]
+    fn check_size_unknown() {
+        let pkg = package("1.0+d8ab0c4");
+        assert_eq!(check(&pkg, b"data"), PackageCheck {
+            size: 4,
+            size_matches: None,
+            hash_matches: Some(false),
+        });
+    }
+
+    #[test]
+    fn check_unknown_hash() {
+        let mut pkg = package("1.0+d8ab0c4");
+        pkg.hash = PackageHash::Unknown("md5:8d777f385d3dfec8815d20f7496026dc"
+                                        .into());
+        assert_eq!(check(&pkg, b"data").hash_matches, None);
+    }
+}
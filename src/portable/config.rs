@@ -6,7 +6,7 @@ use fn_error_context::context;
 
 use crate::commands::ExitCode;
 use crate::portable::exit_codes;
-use crate::portable::repository::{Channel, Query};
+use crate::portable::repository::Query;
 use crate::platform::tmp_file_path;
 use crate::print::{self, echo, Highlight};
 
@@ -80,10 +80,7 @@ pub fn read(path: &Path) -> anyhow::Result<Config> {
         edgedb: Edgedb {
             server_version: val.edgedb.server_version
                 .map(|x| x.into_inner())
-                .unwrap_or(Query {
-                    channel: Channel::Stable,
-                    version: None,
-                }),
+                .unwrap_or_else(Query::latest_stable),
         },
         project: Project{
             schema_dir: val.project
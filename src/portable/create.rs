@@ -86,6 +86,7 @@ pub fn create(options: &Create) -> anyhow::Result<()> {
             name: name.clone(),
             installation: None,
             port,
+            query: None,
         }
     } else {
         let query = Query::from_options(options.nightly, &options.version)?;
@@ -94,6 +95,7 @@ pub fn create(options: &Create) -> anyhow::Result<()> {
             name: name.clone(),
             installation: Some(inst),
             port,
+            query: Some(query),
         };
         bootstrap(&paths, &info,
                   &options.default_database, &options.default_user)?;
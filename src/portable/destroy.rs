@@ -3,6 +3,7 @@ use std::path::{PathBuf};
 use fs_err as fs;
 
 use crate::commands::ExitCode;
+use crate::credentials;
 use crate::options::Options;
 use crate::portable::control;
 use crate::portable::exit_codes;
@@ -48,12 +49,22 @@ pub fn with_projects(name: &str, force: bool,
 
 pub fn destroy(options: &Destroy, opts: &Options) -> anyhow::Result<()> {
     let name = instance_arg(&options.name, &options.instance)?;
+    if options.dry_run {
+        return dry_run(options, opts, name);
+    }
     with_projects(&name, options.force, print_warning, || {
         if !options.force && !options.non_interactive {
-            let q = question::Confirm::new_dangerous(
-                format!("Do you really want to delete instance {:?}?", name)
-            );
-            if !q.ask()? {
+            let cred_path = credentials::path(name)?;
+            let confirmed = match crate::cloud::ops::confirm_destroy(
+                name, &cred_path, opts)?
+            {
+                Some(confirmed) => confirmed,
+                None => question::Confirm::new_dangerous(
+                    format!("Do you really want to delete instance {:?}?",
+                            name)
+                ).ask()?,
+            };
+            if !confirmed {
                 print::error("Canceled.");
                 return Err(ExitCode::new(exit_codes::NOT_CONFIRMED).into());
             }
@@ -73,6 +84,54 @@ pub fn destroy(options: &Destroy, opts: &Options) -> anyhow::Result<()> {
     Ok(())
 }
 
+fn dry_run(options: &Destroy, opts: &Options, name: &str)
+    -> anyhow::Result<()>
+{
+    let paths = local::Paths::get(name)?;
+    log::debug!("Paths {:?}", paths);
+    if cfg!(windows) {
+        if paths.credentials.exists() {
+            crate::cloud::ops::print_destroy_plan(&paths.credentials, opts)?;
+        }
+        return windows::destroy(options);
+    }
+    let mut found = false;
+    if paths.credentials.exists() {
+        found = true;
+        crate::cloud::ops::print_destroy_plan(&paths.credentials, opts)?;
+        echo!("Would remove credentials file",
+              paths.credentials.display());
+    }
+    let dirs = [
+        ("runstate directory", &paths.runstate_dir),
+        ("data directory", &paths.data_dir),
+        ("backup directory", &paths.backup_dir),
+        ("dump", &paths.dump_path),
+        ("upgrade marker", &paths.upgrade_marker),
+    ];
+    for (title, path) in dirs {
+        if path.exists() {
+            found = true;
+            echo!("Would remove", title, path.display());
+        }
+    }
+    for path in &paths.service_files {
+        if path.exists() {
+            found = true;
+            echo!("Would remove service file", path.display());
+        }
+    }
+    for dir in project::find_project_dirs(name)? {
+        echo!("Would unlink project", dir.display());
+    }
+    if !found {
+        print::error(format!("instance {:?} not found", name));
+        return Err(ExitCode::new(exit_codes::INSTANCE_NOT_FOUND).into());
+    }
+    echo!("Dry run: nothing was deleted.");
+    Ok(())
+}
+
 fn do_destroy(options: &Destroy, opts: &Options, name: &str)
     -> anyhow::Result<()>
 {
@@ -154,5 +213,6 @@ pub fn force_by_name(name: &str, options: &Options) -> anyhow::Result<()> {
         force: true,
         quiet: false,
         non_interactive: true,
+        dry_run: false,
     }, options, name)
 }
@@ -0,0 +1,159 @@
+use std::io::{self, Read};
+use std::path::Path;
+use std::str::FromStr;
+
+use async_std::task;
+use fs_err as fs;
+use sha2::Digest;
+
+
+const BUFFER_SIZE: usize = 65536;
+
+/// Hash algorithms which packages can be verified with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashKind {
+    Blake3,
+    Blake2b,
+    Sha256,
+}
+
+/// Incremental hasher for any of the supported algorithms
+pub enum Hasher {
+    Blake3(Box<blake3::Hasher>),
+    Blake2b(blake2b_simd::State),
+    Sha256(sha2::Sha256),
+}
+
+impl Hasher {
+    pub fn new(kind: HashKind) -> Hasher {
+        match kind {
+            HashKind::Blake3 => Hasher::Blake3(Box::new(blake3::Hasher::new())),
+            HashKind::Blake2b => Hasher::Blake2b(blake2b_simd::State::new()),
+            HashKind::Sha256 => Hasher::Sha256(sha2::Sha256::new()),
+        }
+    }
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Blake3(hasher) => { hasher.update(data); }
+            Hasher::Blake2b(hasher) => { hasher.update(data); }
+            Hasher::Sha256(hasher) => hasher.update(data),
+        }
+    }
+    /// Returns raw digest bytes
+    pub fn finalize(self) -> Vec<u8> {
+        match self {
+            Hasher::Blake3(hasher) => hasher.finalize().as_bytes().to_vec(),
+            Hasher::Blake2b(hasher) => hasher.finalize().as_bytes().to_vec(),
+            Hasher::Sha256(hasher) => hasher.finalize().to_vec(),
+        }
+    }
+}
+
+impl FromStr for HashKind {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> anyhow::Result<HashKind> {
+        match s {
+            "blake3" => Ok(HashKind::Blake3),
+            "blake2b" => Ok(HashKind::Blake2b),
+            "sha256" => Ok(HashKind::Sha256),
+            _ => anyhow::bail!("unknown hash algorithm {:?}, \
+                                expected blake3, blake2b or sha256", s),
+        }
+    }
+}
+
+/// Hashes everything read from `reader`, `progress` is called with the
+/// number of bytes after each chunk
+pub fn hash_reader(reader: impl Read, kind: HashKind,
+                   progress: impl FnMut(u64))
+    -> io::Result<Vec<u8>>
+{
+    let mut digests = hash_reader_multi(reader, &[kind], progress)?;
+    Ok(digests.remove(0))
+}
+
+/// Same as `hash_reader` but computes digests of every one of `kinds`
+/// in a single pass over the data
+pub fn hash_reader_multi(mut reader: impl Read, kinds: &[HashKind],
+                         mut progress: impl FnMut(u64))
+    -> io::Result<Vec<Vec<u8>>>
+{
+    let mut hashers = kinds.iter()
+        .map(|&kind| Hasher::new(kind))
+        .collect::<Vec<_>>();
+    let mut buf = vec![0u8; BUFFER_SIZE];
+    loop {
+        let bytes = match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        for hasher in &mut hashers {
+            hasher.update(&buf[..bytes]);
+        }
+        progress(bytes as u64);
+    }
+    Ok(hashers.into_iter().map(Hasher::finalize).collect())
+}
+
+/// Returns raw digest of the file
+pub fn hash_file(path: &Path, kind: HashKind) -> anyhow::Result<Vec<u8>> {
+    let file = fs::File::open(path)?;
+    Ok(hash_reader(file, kind, |_| {})?)
+}
+
+/// Same as `hash_file` but runs on the blocking thread pool, so it doesn't
+/// stall other tasks of the async executor
+pub async fn hash_file_async(path: &Path, kind: HashKind)
+    -> anyhow::Result<Vec<u8>>
+{
+    let path = path.to_path_buf();
+    task::spawn_blocking(move || hash_file(&path, kind)).await
+}
+
+#[cfg(test)]
+mod test {
+    use test_case::test_case;
+
+    use super::{hash_reader, hash_reader_multi, HashKind};
+
+    #[test]
+    fn hash_data() {
+        let hash = |kind| hash_reader(&b"data"[..], kind, |_| {}).unwrap();
+        assert_eq!(&hash(HashKind::Blake3)[..],
+                   blake3::hash(b"data").as_bytes());
+        assert_eq!(&hash(HashKind::Blake2b)[..],
+                   blake2b_simd::blake2b(b"data").as_bytes());
+        assert_eq!(hex::encode(hash(HashKind::Sha256)),
+                   "3a6eb0790f39ac87c94f3856b2dd2c5d\
+                    110e6811602261a9a923d3bb23adc8b7");
+    }
+
+    #[test]
+    fn hash_multi() {
+        let kinds = [HashKind::Sha256, HashKind::Blake3];
+        let digests = hash_reader_multi(&b"data"[..], &kinds, |_| {})
+            .unwrap();
+        assert_eq!(digests.len(), 2);
+        for (kind, digest) in kinds.iter().zip(digests) {
+            assert_eq!(digest,
+                       hash_reader(&b"data"[..], *kind, |_| {}).unwrap());
+        }
+    }
+
+    #[test]
+    fn hash_progress() {
+        let mut total = 0;
+        hash_reader(&[0u8; 100_000][..], HashKind::Blake3, |n| total += n)
+            .unwrap();
+        assert_eq!(total, 100_000);
+    }
+
+    #[test_case("blake3" => Some(HashKind::Blake3))]
+    #[test_case("sha256" => Some(HashKind::Sha256))]
+    #[test_case("md5" => None)]
+    fn parse_kind(val: &str) -> Option<HashKind> {
+        val.parse().ok()
+    }
+}
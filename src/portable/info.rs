@@ -1,8 +1,16 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
 use anyhow::Context;
+use indicatif::HumanBytes;
 
-use crate::portable::local;
-use crate::portable::options::Info;
+use crate::platform::data_dir;
+use crate::portable::local::{self, InstallInfo, InstanceInfo};
+use crate::portable::options::{Info, ResolveQuery};
 use crate::portable::repository::Query;
+use crate::portable::status::{instance_status, list_local, status_str};
+use crate::portable::status::Service;
 use crate::portable::ver;
 use crate::table;
 
@@ -12,38 +20,302 @@ use crate::table;
 struct JsonInfo<'a> {
     version: &'a ver::Build,
     binary_path: Option<&'a str>,
+    build_date: Option<String>,
+    installed_at: String,
+    data_dir: Option<String>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    data_size_bytes: Option<u64>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    instance_data_sizes: Option<Vec<JsonDataSize>>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    status: Option<Vec<JsonInstanceStatus>>,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all="kebab-case")]
+struct JsonDataSize {
+    name: String,
+    size_bytes: u64,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all="kebab-case")]
+struct JsonInstanceStatus {
+    name: String,
+    status: &'static str,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pid: Option<u32>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    port: Option<u16>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    query: Option<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("One of `--latest`, `--nightly`, `--version=` required")]
+pub struct FilterRequired;
+
+
+/// Modification time of the binary, which is preserved from the package
+/// archive, so it's the time the server was built
+fn build_date(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified())
+        .map_err(|e| log::info!("Cannot read mtime of {:?}: {}", path, e))
+        .ok()
+}
+
+/// Local instances running the given version
+fn instances_using(version: &ver::Build) -> anyhow::Result<Vec<InstanceInfo>> {
+    let mut result = Vec::new();
+    let dir = data_dir()?;
+    // on windows data lives inside WSL
+    if cfg!(windows) || !dir.exists() {
+        return Ok(result);
+    }
+    for pair in list_local(&dir)? {
+        let (name, _) = pair?;
+        match InstanceInfo::try_read(&name) {
+            Ok(Some(info)) if info.get_version().ok() == Some(version) => {
+                result.push(info);
+            }
+            Ok(_) => {}
+            Err(e) => log::info!("Skipping instance {:?}: {:#}", name, e),
+        }
+    }
+    result.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(result)
+}
+
+/// Total size of files in the directory, not following symlinks
+///
+/// Data directory of a running instance changes while it's walked, so
+/// entries that vanished or can't be read are skipped.
+fn dir_size(dir: &Path) -> u64 {
+    let mut size = 0;
+    let mut queue = vec![dir.to_path_buf()];
+    while let Some(dir) = queue.pop() {
+        let items = match fs::read_dir(&dir) {
+            Ok(items) => items,
+            Err(e) => {
+                log::info!("Skipping directory {:?}: {}", dir, e);
+                continue;
+            }
+        };
+        for item in items {
+            let entry = item
+                .and_then(|item| Ok((item.path(), item.metadata()?)));
+            let (path, meta) = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    log::info!("Skipping entry of {:?}: {}", dir, e);
+                    continue;
+                }
+            };
+            if meta.is_dir() {
+                queue.push(path);
+            } else {
+                size += meta.len();
+            }
+        }
+    }
+    size
+}
+
+/// Sizes of data directories of the instances
+fn data_sizes(instances: &[InstanceInfo])
+    -> anyhow::Result<Vec<JsonDataSize>>
+{
+    instances.iter().map(|inst| {
+        Ok(JsonDataSize {
+            name: inst.name.clone(),
+            size_bytes: dir_size(&inst.data_dir()?),
+        })
+    }).collect()
+}
+
+fn format_data_sizes(sizes: &[JsonDataSize]) -> String {
+    if sizes.is_empty() {
+        return "<no instances>".into();
+    }
+    let total = sizes.iter().map(|s| s.size_bytes).sum::<u64>();
+    format!("{} ({})", HumanBytes(total), sizes.iter()
+        .map(|s| format!("{}: {}", s.name, HumanBytes(s.size_bytes)))
+        .collect::<Vec<_>>().join(", "))
 }
 
+fn instance_statuses(instances: &[InstanceInfo])
+    -> anyhow::Result<Vec<JsonInstanceStatus>>
+{
+    instances.iter().map(|inst| {
+        let status = instance_status(&inst.name)?;
+        let (pid, port) = match status.service {
+            Service::Running { pid } => (Some(pid), Some(inst.port)),
+            _ => (None, None),
+        };
+        Ok(JsonInstanceStatus {
+            name: inst.name.clone(),
+            status: status_str(&status.service),
+            pid,
+            port,
+            query: inst.query.as_ref().map(|q| q.as_config_value()),
+        })
+    }).collect()
+}
+
+fn format_status(statuses: &[JsonInstanceStatus]) -> String {
+    if statuses.is_empty() {
+        return "<no instances>".into();
+    }
+    statuses.iter().map(|st| match (st.pid, st.port) {
+        (Some(pid), Some(port)) => {
+            format!("{}: {} (pid {}, port {})", st.name, st.status, pid, port)
+        }
+        _ => format!("{}: {}", st.name, st.status),
+    }).collect::<Vec<_>>().join(", ")
+}
+
+fn format_time(time: SystemTime) -> String {
+    humantime::format_rfc3339_seconds(time).to_string()
+}
+
+fn print_formatted<T: serde::Serialize>(format: &str, data: &T)
+    -> anyhow::Result<()>
+{
+    if format == "yaml" {
+        println!("{}", serde_yaml::to_string(data)?);
+    } else {
+        println!("{}", serde_json::to_string_pretty(data)?);
+    }
+    Ok(())
+}
+
+fn print_table(inst: &InstallInfo, data_dir: Option<&PathBuf>,
+               options: &Info)
+    -> anyhow::Result<()>
+{
+    let path = inst.server_path()?;
+    let instances = instances_using(&inst.version)?;
+    let mut rows = vec![
+        ("Version", inst.version.to_string()),
+        ("Binary path", path.display().to_string()),
+        ("Build date", build_date(&path).map(format_time)
+            .unwrap_or_else(|| "<unknown>".into())),
+        ("Installed at", format_time(inst.installed_at)),
+        ("Data directory", data_dir.map(|d| d.display().to_string())
+            .unwrap_or_else(|| "<none>".into())),
+    ];
+    if options.data_size {
+        rows.push(("Data size", format_data_sizes(&data_sizes(&instances)?)));
+    }
+    if options.status {
+        rows.push(("Status", format_status(&instance_statuses(&instances)?)));
+    }
+    table::settings(&rows.iter()
+        .map(|(title, value)| (*title, &value[..]))
+        .collect::<Vec<_>>());
+    Ok(())
+}
 
 pub fn info(options: &Info) -> anyhow::Result<()> {
-    if !options.nightly && !options.latest && !options.version.is_some() {
-        anyhow::bail!("One of `--latest`, `--nightly`, `--version=` required");
+    let no_filter = !options.nightly && !options.latest
+        && !options.version.is_some();
+    if no_filter && !options.all {
+        return Err(FilterRequired.into());
     }
     // note this assumes that latest is set if no nightly and version
     let query = Query::from_options(options.nightly, &options.version)?;
-    let all = local::get_installed()?;
-    let inst = all.into_iter().filter(|item| query.matches(&item.version))
-        .max_by_key(|item| item.version.specific())
-        .context("cannot find installed packages maching your criteria")?;
+    let mut installed = local::get_installed()?.into_iter()
+        // `--all` without a filter shows every installed version
+        .filter(|item| {
+            (options.all && no_filter) || query.matches(&item.version)
+        })
+        .collect::<Vec<_>>();
+    if installed.is_empty() {
+        anyhow::bail!("cannot find installed packages maching your criteria");
+    }
+    installed.sort_by_key(|item| item.version.specific());
+    if !options.all {
+        installed.drain(..installed.len() - 1);
+    }
+    let format = if options.json {
+        Some("json")
+    } else {
+        options.format.as_deref()
+    };
+    let paths = installed.iter()
+        .map(|inst| inst.server_path())
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let data_dir = Some(data_dir()?).filter(|dir| !cfg!(windows) && dir.exists());
     if options.bin_path {
-        let path = inst.server_path()?;
-        if options.json {
-            let path = path.to_str()
-                .context("cannot convert path to a string")?;
-            println!("{}", serde_json::to_string(path)?);
+        if format == Some("json") {
+            let paths = paths.iter()
+                .map(|path| {
+                    path.to_str().context("cannot convert path to a string")
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            if options.all {
+                println!("{}", serde_json::to_string(&paths)?);
+            } else {
+                println!("{}", serde_json::to_string(paths[0])?);
+            }
         } else {
-            println!("{}", path.display());
+            for path in &paths {
+                println!("{}", path.display());
+            }
+        }
+    } else if let Some(format) = format {
+        let infos = installed.iter().zip(&paths)
+            .map(|(inst, path)| {
+                let instances = instances_using(&inst.version)?;
+                let sizes = if options.data_size {
+                    Some(data_sizes(&instances)?)
+                } else {
+                    None
+                };
+                Ok(JsonInfo {
+                    version: &inst.version,
+                    binary_path: path.to_str(),
+                    build_date: build_date(path).map(format_time),
+                    installed_at: format_time(inst.installed_at),
+                    data_dir: data_dir.as_ref()
+                        .and_then(|d| d.to_str()).map(String::from),
+                    data_size_bytes: sizes.as_ref().map(|sizes| {
+                        sizes.iter().map(|s| s.size_bytes).sum()
+                    }),
+                    instance_data_sizes: sizes,
+                    status: if options.status {
+                        Some(instance_statuses(&instances)?)
+                    } else {
+                        None
+                    },
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        if options.all {
+            print_formatted(format, &infos)?;
+        } else {
+            print_formatted(format, &infos[0])?;
         }
-    } else if options.json {
-        println!("{}", serde_json::to_string_pretty(&JsonInfo {
-            version: &inst.version,
-            binary_path: inst.server_path()?.to_str(),
-        })?)
     } else {
-        table::settings(&[
-            ("Version", &inst.version.to_string()),
-            ("Binary path", &inst.server_path()?.display().to_string()),
-        ]);
+        for (idx, inst) in installed.iter().enumerate() {
+            if idx > 0 {
+                println!();
+            }
+            print_table(inst, data_dir.as_ref(), options)?;
+        }
     }
     Ok(())
 }
+
+pub fn resolve_query(options: &ResolveQuery) -> anyhow::Result<()> {
+    let query = Query::from_options(options.nightly, &options.version)?;
+    table::settings(&[
+        ("Channel", query.channel.as_str()),
+        ("Version filter", &query.version.as_ref()
+            .map(|v| v.to_string())
+            .unwrap_or_else(|| "<none>".into())),
+        ("Query", &query.display().to_string()),
+        ("Config value", &query.as_config_value()),
+    ]);
+    Ok(())
+}
@@ -6,20 +6,46 @@ use std::time::SystemTime;
 use anyhow::Context;
 use async_std::task;
 use fn_error_context::context;
-use indicatif::{ProgressBar, ProgressStyle};
+use indicatif::{HumanBytes, ProgressBar, ProgressStyle};
 
 use crate::commands::ExitCode;
 use crate::platform;
+use crate::portable::cache;
 use crate::portable::exit_codes;
 use crate::portable::local::{InstallInfo, write_json};
-use crate::portable::options::Install;
-use crate::portable::platform::optional_docker_check;
-use crate::portable::repository::{PackageInfo, PackageHash, Query, download};
+use crate::portable::options::{Install, InstallFromUrl};
+use crate::portable::platform::{optional_docker_check, get_server};
+use crate::portable::repository::{PackageInfo, PackageHash, PackageType};
+use crate::portable::repository::{Query, download_verified, verify_download};
+use crate::portable::repository::download_all;
 use crate::portable::repository::{get_server_package, get_specific_package};
+use crate::portable::repository::get_server_package_at_least;
+use crate::portable::repository::{download_segmented, download_segments};
+use crate::portable::repository::{set_offline, set_rate_limit, pkg_cache_dir};
+use crate::portable::repository::set_no_progress;
+use crate::portable::signature;
+use crate::portable::repository::{Channel, platform_pkg_root};
 use crate::portable::ver;
 use crate::print::{self, echo, Highlight};
+use crate::table;
 
 
+#[derive(serde::Serialize)]
+#[serde(rename_all="kebab-case")]
+struct JsonDryRun<'a> {
+    package: &'a PackageInfo,
+    channel: Channel,
+    mirror: &'a url::Url,
+    cache_path: &'a Path,
+    cache_hit: bool,
+    installed: bool,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("package was downloaded and verified, but extraction failed \
+         (disk is full or archive is damaged)")]
+pub struct ExtractionFailed;
+
 #[context("metadata error for {:?}", dir)]
 fn check_metadata(dir: &Path, pkg_info: &PackageInfo)
     -> anyhow::Result<InstallInfo>
@@ -35,30 +61,104 @@ fn check_metadata(dir: &Path, pkg_info: &PackageInfo)
     Ok(data)
 }
 
+/// Resolves the query and returns package with a verified local path to it
+///
+/// Package that is already in `cache_dir` is reused if its hash matches.
+pub fn fetch(query: &Query, cache_dir: &Path)
+    -> anyhow::Result<(PackageInfo, PathBuf)>
+{
+    let pkg_info = get_server_package(query)?
+        .context("no package matching your criteria found")?;
+    let (path, _) = fetch_package(&pkg_info, cache_dir)?;
+    Ok((pkg_info, path))
+}
+
+/// Same as `fetch` but for an already resolved package, also returns
+/// whether the package was downloaded rather than found in the cache
 #[context("failed to download {}", pkg_info)]
-fn download_package(pkg_info: &PackageInfo)
-    -> anyhow::Result<PathBuf>
+pub fn fetch_package(pkg_info: &PackageInfo, cache_dir: &Path)
+    -> anyhow::Result<(PathBuf, bool)>
 {
-    let cache_dir = platform::cache_dir()?;
-    let download_dir = cache_dir.join("downloads");
-    fs::create_dir_all(&download_dir)?;
-    let cache_path = download_dir.join(pkg_info.cache_file_name());
-    let hash = task::block_on(download(&cache_path, &pkg_info.url,
-                                       false, true))?;
-    match &pkg_info.hash {
-        PackageHash::Blake2b(hex) => {
-            if hash.to_hex()[..] != hex[..] {
-                anyhow::bail!("hash mismatch {} != {}", hash.to_hex(), hex);
-            }
-        }
-        PackageHash::Unknown(val) => {
-            log::warn!("Cannot verify hash, unknown hash format {:?}", val);
-        }
+    fs::create_dir_all(&cache_dir)?;
+    let cache_path = cache_dir.join(pkg_info.cache_file_name());
+    migrate_legacy_cache(pkg_info, cache_dir, &cache_path)?;
+    let downloaded = if cache_path.exists()
+        && cache::verify_package(&cache_path, pkg_info)?
+    {
+        log::info!("Using cached package {:?}", cache_path);
+        false
+    } else {
+        print_download_message(pkg_info);
+        download_to(pkg_info, &cache_path)?;
+        true
+    };
+    if signature::is_required() {
+        signature::verify_package(pkg_info, &cache_path).map_err(|e| {
+            unlink_cache(&cache_path);
+            e
+        })?;
     }
-    Ok(cache_path)
+    Ok((cache_path, downloaded))
 }
 
-fn build_path(base: &Path, path: &Path) -> anyhow::Result<Option<PathBuf>> {
+/// Tells user what is going to be downloaded, including the size if known
+fn print_download_message(pkg_info: &PackageInfo) {
+    if pkg_info.size > 0 {
+        echo!("Downloading edgedb-server", pkg_info.version.emphasize(),
+              format_args!("({})...", HumanBytes(pkg_info.size)));
+    } else {
+        echo!("Downloading edgedb-server", pkg_info.version.emphasize(); "...");
+    }
+}
+
+fn download_to(pkg_info: &PackageInfo, cache_path: &Path)
+    -> anyhow::Result<()>
+{
+    let segments = download_segments();
+    // size is unknown for packages installed from URL,
+    // and packages from EDGEDB_PKG_CACHE are copied rather than downloaded
+    if segments > 1 && pkg_info.size > 0 && pkg_cache_dir().is_none() {
+        task::block_on(async {
+            let digest = download_segmented(cache_path, &pkg_info.url,
+                                            pkg_info.size, segments,
+                                            pkg_info.hash.kind(),
+                                            false).await?;
+            verify_download(cache_path, &digest, &pkg_info.hash).await
+        })?;
+    } else {
+        task::block_on(download_verified(cache_path, &pkg_info.url,
+                                         &pkg_info.hash,
+                                         pkg_info.expected_size(),
+                                         false, true))?;
+    }
+    Ok(())
+}
+
+/// Renames package cached under the old short-hash name if it's valid,
+/// invalid ones are removed as they would never be used again
+fn migrate_legacy_cache(pkg_info: &PackageInfo, cache_dir: &Path,
+                        cache_path: &Path)
+    -> anyhow::Result<()>
+{
+    let legacy_path = cache_dir.join(pkg_info.legacy_cache_file_name());
+    if !legacy_path.exists() || cache_path.exists() {
+        return Ok(());
+    }
+    if cache::verify_package(&legacy_path, pkg_info)? {
+        log::info!("Renaming cached package {:?} -> {:?}",
+                   legacy_path, cache_path);
+        fs::rename(&legacy_path, cache_path)?;
+    } else {
+        log::info!("Removing outdated cached package {:?}", legacy_path);
+        fs::remove_file(&legacy_path)?;
+    }
+    Ok(())
+}
+
+/// Returns path of the archive entry relative to the installation directory
+///
+/// Returns `None` for the root and the package directory itself.
+pub fn package_relative_path(path: &Path) -> anyhow::Result<Option<PathBuf>> {
     let mut components = path.components()
         .filter_map(|part| {
             match part {
@@ -81,15 +181,27 @@ fn build_path(base: &Path, path: &Path) -> anyhow::Result<Option<PathBuf>> {
     } else {
         return Ok(None); // skipping root
     }
+    let mut result = PathBuf::new();
+    for component in components {
+        result.push(component?);
+    }
+    if result.as_os_str().is_empty() {
+        return Ok(None); // the package directory itself
+    }
+    Ok(Some(result))
+}
 
+fn build_path(base: &Path, path: &Path) -> anyhow::Result<Option<PathBuf>> {
+    let relative = match package_relative_path(path)? {
+        Some(relative) => relative,
+        None => return Ok(None),
+    };
+    let mut components = relative.components();
     let mut dest = PathBuf::from(base);
     if let Some(component) = components.next() {
-        dest.push(component?);
-    } else {
-        return Ok(None); // the package directory itself
+        dest.push(component);
     }
     for component in components {
-        let component = component?;
         match dest.symlink_metadata() {
             Ok(m) if m.file_type().is_symlink() => {
                 anyhow::bail!("cannot unpack {:?} to the symlinked dir {:?}",
@@ -110,7 +222,7 @@ fn build_path(base: &Path, path: &Path) -> anyhow::Result<Option<PathBuf>> {
 }
 
 #[context("failed to unpack {:?} -> {:?}", cache_file, target_dir)]
-fn unpack_package(cache_file: &Path, target_dir: &Path)
+pub fn unpack_package(cache_file: &Path, target_dir: &Path)
     -> anyhow::Result<()>
 {
     if target_dir.exists() {
@@ -121,6 +233,10 @@ fn unpack_package(cache_file: &Path, target_dir: &Path)
     // needed for long paths on windows
     let target_dir = target_dir.canonicalize()?;
 
+    if cache_file.extension().map(|ext| ext == "zip").unwrap_or(false) {
+        return unpack_zip(cache_file, &target_dir);
+    }
+
     let file = fs::File::open(&cache_file)?;
     let bar = ProgressBar::new(file.metadata()?.len());
     bar.set_style(
@@ -141,6 +257,55 @@ fn unpack_package(cache_file: &Path, target_dir: &Path)
     Ok(())
 }
 
+fn unpack_zip(cache_file: &Path, target_dir: &Path) -> anyhow::Result<()> {
+    let mut zip = zip::ZipArchive::new(
+        io::BufReader::new(fs::File::open(&cache_file)?))?;
+    let bar = ProgressBar::new(zip.len() as u64);
+    bar.set_style(
+        ProgressStyle::default_bar()
+        .template("Unpacking [{bar}] {pos}/{len} files")
+        .progress_chars("=> "));
+    for index in 0..zip.len() {
+        let mut entry = zip.by_index(index)?;
+        let name = entry.enclosed_name()
+            .with_context(|| format!("invalid path {:?} in archive",
+                                     entry.name()))?
+            .to_path_buf();
+        if let Some(path) = build_path(&target_dir, &name)? {
+            if entry.is_dir() {
+                fs::create_dir_all(&path)?;
+            } else {
+                let mut out = fs::File::create(&path)?;
+                io::copy(&mut entry, &mut out)?;
+                #[cfg(unix)]
+                if let Some(mode) = entry.unix_mode() {
+                    use std::os::unix::fs::PermissionsExt;
+                    fs::set_permissions(&path,
+                                        fs::Permissions::from_mode(mode))?;
+                }
+            }
+        }
+        bar.inc(1);
+    }
+    bar.finish_and_clear();
+    Ok(())
+}
+
+/// Unpacks verified package, removing partially extracted files on error
+pub fn extract_package(cache_file: &Path, target_dir: &Path)
+    -> anyhow::Result<()>
+{
+    unpack_package(cache_file, target_dir).map_err(|e| {
+        if target_dir.exists() {
+            fs::remove_dir_all(target_dir)
+                .map_err(|e| {
+                    log::warn!("Failed to remove {:?}: {}", target_dir, e);
+                }).ok();
+        }
+        e.context(ExtractionFailed)
+    })
+}
+
 fn unlink_cache(cache_file: &Path) {
     fs::remove_file(&cache_file)
         .map_err(|e| {
@@ -155,8 +320,102 @@ pub fn install(options: &Install) -> anyhow::Result<()> {
         );
         return Err(ExitCode::new(exit_codes::DOCKER_CONTAINER))?;
     }
-    let query = Query::from_options(options.nightly, &options.version)?;
-    version(&query)?;
+    if let Some(rate) = options.limit_rate {
+        set_rate_limit(rate);
+    }
+    set_offline(options.offline);
+    set_no_progress(options.no_progress);
+    signature::set_required(options.verify_signature);
+    if options.version.len() > 1 {
+        return install_versions(options);
+    }
+    let version = options.version.first().cloned();
+    let (query, pkg_info) = if let Some(min_version) = &options.at_least {
+        let query = if options.nightly {
+            Query::latest_nightly()
+        } else {
+            Query::from_version(min_version)?
+        };
+        let pkg_info = get_server_package_at_least(query.channel,
+                                                   min_version)?
+            .with_context(|| {
+                format!("no package of version {} or newer found",
+                        min_version)
+            })?;
+        (query, pkg_info)
+    } else {
+        let query = Query::from_options(options.nightly, &version)?;
+        if options.keep_download && !options.dry_run {
+            // the package is kept even if the version is installed already
+            let (pkg_info, cache_path) = fetch(&query,
+                                               &cache::download_dir()?)?;
+            install_fetched(&pkg_info, &cache_path)?;
+            echo!("Downloaded package is kept at", cache_path.display());
+            return Ok(());
+        }
+        let pkg_info = get_server_package(&query)?
+            .context("no package matching your criteria found")?;
+        (query, pkg_info)
+    };
+    if options.dry_run {
+        return dry_run(&query, &pkg_info, options.json);
+    }
+    install_package(&pkg_info, options.keep_download)?;
+    Ok(())
+}
+
+/// Installs every version passed with `--version`
+fn install_versions(options: &Install) -> anyhow::Result<()> {
+    if options.dry_run {
+        anyhow::bail!("`--dry-run` works with a single `--version`");
+    }
+    let pkgs = options.version.iter()
+        .map(|ver| {
+            let query = Query::from_options(false, &Some(ver.clone()))?;
+            get_server_package(&query)?.with_context(|| {
+                format!("no package matching version {} found", ver)
+            })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    packages(&pkgs, options.keep_download)?;
+    Ok(())
+}
+
+/// Prints which package `query` resolves to and whether it's already
+/// cached or installed
+pub fn dry_run(query: &Query, pkg_info: &PackageInfo, json: bool)
+    -> anyhow::Result<()>
+{
+    let ver_name = pkg_info.version.specific().to_string();
+    let installed = platform::portable_dir()?.join(&ver_name).exists();
+    let cache_path = cache::download_dir()?.join(pkg_info.cache_file_name());
+    let cache_hit = cache_path.exists()
+        && cache::verify_package(&cache_path, pkg_info)?;
+    let mirror = platform_pkg_root(get_server()?)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&JsonDryRun {
+            package: pkg_info,
+            channel: query.channel,
+            mirror: &mirror,
+            cache_path: &cache_path,
+            cache_hit,
+            installed,
+        })?);
+    } else {
+        table::settings(&[
+            ("Version", &pkg_info.version.to_string()),
+            ("Channel", query.channel.as_str()),
+            ("Mirror", mirror.as_str()),
+            ("URL", pkg_info.url.as_str()),
+            ("Size", &pkg_info.expected_size()
+                .map(|size| HumanBytes(size).to_string())
+                .unwrap_or_else(|| "unknown".into())),
+            ("Hash", &pkg_info.hash.to_string()),
+            ("Cache path", &cache_path.display().to_string()),
+            ("Cached", if cache_hit { "yes" } else { "no" }),
+            ("Installed", if installed { "yes" } else { "no" }),
+        ]);
+    }
     Ok(())
 }
 
@@ -176,19 +435,117 @@ pub fn specific(version: &ver::Specific) -> anyhow::Result<InstallInfo> {
     package(&pkg)
 }
 
+pub fn install_from_url(options: &InstallFromUrl)
+    -> anyhow::Result<InstallInfo>
+{
+    let kind = PackageType::from_url(&options.url)
+        .with_context(|| format!("cannot detect package type of {}, \
+                                  URL must end with `.tar.zst` or `.zip`",
+                                  options.url))?;
+    let hash = if let Some(hash) = &options.blake2b {
+        let hash = hash.to_ascii_lowercase();
+        if hash.len() != 128 || hex::decode(&hash).is_err() {
+            anyhow::bail!("blake2b hash must be 128 hex characters");
+        }
+        PackageHash::Blake2b(hash.into())
+    } else {
+        print::warn("Package hash is not verified. \
+                     Only install packages from sources you trust.");
+        // gives the cache file a readable name, and `cache verify`
+        // reports it as unverifiable rather than valid
+        PackageHash::Unknown("unverified".into())
+    };
+    let pkg_info = PackageInfo {
+        version: options.version.clone(),
+        url: options.url.clone(),
+        size: 0,
+        hash,
+        kind,
+        signature: None,
+    };
+    install_package(&pkg_info, false)
+}
+
 pub fn package(pkg_info: &PackageInfo) -> anyhow::Result<InstallInfo> {
+    install_package(pkg_info, false)
+}
+
+/// Installs several packages, the ones which are neither installed nor
+/// cached are downloaded concurrently first
+pub fn packages(pkgs: &[PackageInfo], keep_download: bool)
+    -> anyhow::Result<Vec<InstallInfo>>
+{
+    let cache_dir = cache::download_dir()?;
+    fs::create_dir_all(&cache_dir)?;
+    let mut targets: Vec<(async_std::path::PathBuf, url::Url, PackageHash)>
+        = Vec::new();
+    for pkg_info in pkgs {
+        let ver_name = pkg_info.version.specific().to_string();
+        if platform::portable_dir()?.join(&ver_name).exists() &&
+            !keep_download
+        {
+            continue;
+        }
+        let cache_path = cache_dir.join(pkg_info.cache_file_name());
+        migrate_legacy_cache(pkg_info, &cache_dir, &cache_path)?;
+        let dest = async_std::path::PathBuf::from(cache_path.clone());
+        if targets.iter().any(|(path, _, _)| path == &dest) ||
+            cache_path.exists() &&
+            cache::verify_package(&cache_path, pkg_info)?
+        {
+            continue;
+        }
+        print_download_message(pkg_info);
+        targets.push((dest, pkg_info.url.clone(), pkg_info.hash.clone()));
+    }
+    download_all(&targets)?;
+    pkgs.iter().map(|pkg| install_package(pkg, keep_download)).collect()
+}
+
+fn install_package(pkg_info: &PackageInfo, keep_download: bool)
+    -> anyhow::Result<InstallInfo>
+{
+    if let Some(meta) = installed(pkg_info)? {
+        if keep_download {
+            let (cache_path, _) = fetch_package(pkg_info,
+                                                &cache::download_dir()?)?;
+            echo!("Downloaded package is kept at", cache_path.display());
+        }
+        return Ok(meta);
+    }
+    let (cache_path, _) = fetch_package(pkg_info, &cache::download_dir()?)?;
+    let info = install_fetched(pkg_info, &cache_path)?;
+    if keep_download {
+        echo!("Downloaded package is kept at", cache_path.display());
+    } else {
+        unlink_cache(&cache_path);
+    }
+    Ok(info)
+}
+
+/// Returns metadata of the package if its version is already installed
+fn installed(pkg_info: &PackageInfo) -> anyhow::Result<Option<InstallInfo>> {
     let ver_name = pkg_info.version.specific().to_string();
     let target_dir = platform::portable_dir()?.join(&ver_name);
-    if target_dir.exists() {
-        let meta = check_metadata(&target_dir, &pkg_info)?;
-        echo!("Version", meta.version.emphasize(), "is already installed");
-        return Ok(meta);
+    if !target_dir.exists() {
+        return Ok(None);
     }
+    let meta = check_metadata(&target_dir, &pkg_info)?;
+    echo!("Version", meta.version.emphasize(), "is already installed");
+    Ok(Some(meta))
+}
 
-    echo!("Downloading package...");
-    let cache_path = download_package(&pkg_info)?;
+/// Installs package from `cache_path` unless its version is installed
+fn install_fetched(pkg_info: &PackageInfo, cache_path: &Path)
+    -> anyhow::Result<InstallInfo>
+{
+    if let Some(meta) = installed(pkg_info)? {
+        return Ok(meta);
+    }
+    let ver_name = pkg_info.version.specific().to_string();
+    let target_dir = platform::portable_dir()?.join(&ver_name);
     let tmp_target = platform::tmp_file_path(&target_dir);
-    unpack_package(&cache_path, &tmp_target)?;
+    extract_package(&cache_path, &tmp_target)?;
     let info = InstallInfo {
         version: pkg_info.version.clone(),
         package_url: pkg_info.url.clone(),
@@ -198,8 +555,25 @@ pub fn package(pkg_info: &PackageInfo) -> anyhow::Result<InstallInfo> {
     write_json(&tmp_target.join("install_info.json"), "metadata", &info)?;
     fs::rename(&tmp_target, &target_dir).with_context(
         || format!("cannot rename {:?} -> {:?}", tmp_target, target_dir))?;
-    unlink_cache(&cache_path);
     echo!("Successfully installed", pkg_info.version.emphasize());
 
     Ok(info)
 }
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::{extract_package, ExtractionFailed};
+
+    #[test]
+    fn extraction_failure_cleans_up() {
+        let tmp = tempfile::tempdir().unwrap();
+        let archive = tmp.path().join("broken.tar.zst");
+        fs::write(&archive, b"definitely not a zstd frame").unwrap();
+        let target = tmp.path().join("target");
+        let err = extract_package(&archive, &target).unwrap_err();
+        assert!(err.is::<ExtractionFailed>());
+        assert!(!target.exists());
+    }
+}
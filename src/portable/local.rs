@@ -12,7 +12,7 @@ use edgedb_client::Builder;
 use crate::bug;
 use crate::credentials;
 use crate::platform::{portable_dir, data_dir, config_dir, cache_dir};
-use crate::portable::repository::PackageHash;
+use crate::portable::repository::{PackageHash, Query};
 use crate::portable::ver;
 use crate::portable::{windows, linux, macos};
 
@@ -37,6 +37,10 @@ pub struct InstanceInfo {
     pub name: String,
     pub installation: Option<InstallInfo>,
     pub port: u16,
+    /// Version query the instance was created or last upgraded with,
+    /// used as the default constraint on upgrade
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub query: Option<Query>,
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -5,6 +5,7 @@ use crate::options::Options;
 use crate::portable::project::ProjectCommand;
 use crate::portable::options::{ServerCommand, ServerInstanceCommand};
 
+use crate::portable::cache;
 use crate::portable::control;
 use crate::portable::create;
 use crate::portable::credentials;
@@ -13,11 +14,13 @@ use crate::portable::info;
 use crate::portable::install;
 use crate::portable::link;
 use crate::portable::list_versions;
+use crate::portable::ping;
 use crate::portable::project;
 use crate::portable::revert;
 use crate::portable::status;
 use crate::portable::uninstall;
 use crate::portable::upgrade;
+use crate::portable::verify_install;
 use crate::portable::reset_password;
 use crate::portable::windows;
 
@@ -28,12 +31,26 @@ pub fn server_main(cmd: &ServerCommand) -> Result<(), anyhow::Error> {
     match &cmd.subcommand {
         Install(c) if cfg!(windows) => windows::install(c),
         Install(c) => install::install(c),
+        InstallFromUrl(c) if cfg!(windows) => windows::install_from_url(c),
+        InstallFromUrl(c) => install::install_from_url(c).map(|_| ()),
         Uninstall(c) if cfg!(windows) => windows::uninstall(c),
         Uninstall(c) => uninstall::uninstall(c),
         ListVersions(c) if cfg!(windows) => windows::list_versions(c),
         ListVersions(c) => list_versions::list_versions(c),
         Info(c) if cfg!(windows) => windows::info(c),
         Info(c) => info::info(c),
+        ListCache(c) if cfg!(windows) => windows::list_cache(c),
+        ListCache(c) => cache::list_cache(c),
+        VerifyCache(c) if cfg!(windows) => windows::verify_cache(c),
+        VerifyCache(c) => cache::verify_cache(c),
+        CachePath(c) if cfg!(windows) => windows::cache_path(c),
+        CachePath(c) => cache::cache_path(c),
+        CheckPackage(c) if cfg!(windows) => windows::check_package(c),
+        CheckPackage(c) => cache::check_package(c),
+        VerifyInstall(c) if cfg!(windows) => windows::verify_install(c),
+        VerifyInstall(c) => verify_install::verify_install(c),
+        ResolveQuery(c) if cfg!(windows) => windows::resolve_query(c),
+        ResolveQuery(c) => info::resolve_query(c),
     }
 }
 
@@ -67,6 +84,7 @@ pub fn instance_main(cmd: &ServerInstanceCommand, options: &Options)
         Status(c) if cfg!(windows) => windows::status(c),
         Status(c) => status::status(c),
         Credentials(c) => credentials::show_credentials(&options, &c),
+        Ping(c) => ping::ping(c),
     }
 }
 
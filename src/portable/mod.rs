@@ -11,19 +11,25 @@ pub mod macos;
 pub mod linux;
 pub mod windows;
 
+mod cache;
 mod control;
 mod create;
 mod credentials;
 mod destroy;
+mod hash;
 mod info;
 mod install;
 mod link;
 mod list_versions;
+mod ping;
+mod proxy;
 mod reset_password;
 mod revert;
+mod signature;
 mod status;
 mod uninstall;
 mod upgrade;
+mod verify_install;
 pub mod project;
 
 pub use main::{instance_main, server_main, project_main};
@@ -1,4 +1,5 @@
 use std::fmt;
+use std::path::PathBuf;
 use std::str::FromStr;
 
 use clap::{ValueHint};
@@ -7,6 +8,7 @@ use edgedb_cli_derive::{EdbClap, IntoArgs};
 
 use crate::commands::ExitCode;
 use crate::portable::local::is_valid_name;
+use crate::portable::repository::parse_size;
 use crate::portable::ver;
 use crate::print::{echo, warn, err_marker};
 use crate::process::{self, IntoArg};
@@ -60,6 +62,8 @@ pub enum InstanceCommand {
     /// Echo credentials to connect to the instance
     #[edb(inherit(crate::options::ConnectionOptions))]
     Credentials(ShowCredentials),
+    /// Check that the instance is reachable using stored credentials
+    Ping(Ping),
 }
 
 #[derive(EdbClap, Clone, Debug)]
@@ -68,10 +72,25 @@ pub enum Command {
     Info(Info),
     /// Install an EdgeDB version locally
     Install(Install),
+    /// Install a package from a direct URL (e.g. a custom build)
+    InstallFromUrl(InstallFromUrl),
     /// Uninstall an EdgeDB version locally
     Uninstall(Uninstall),
     /// List available and installed versions of EdgeDB
     ListVersions(ListVersions),
+    /// List downloaded packages in the cache directory
+    ListCache(ListCache),
+    /// Verify hashes of all downloaded packages in the cache directory
+    VerifyCache(VerifyCache),
+    /// Show where the package matching the criteria is (or would be) cached
+    CachePath(CachePath),
+    /// Check a package downloaded elsewhere against the repository index
+    CheckPackage(CheckPackage),
+    /// Check installed files against the package and optionally repair them
+    VerifyInstall(VerifyInstall),
+    /// Show which query the version options resolve to (for debugging)
+    #[edb(hide=true)]
+    ResolveQuery(ResolveQuery),
 }
 
 #[derive(EdbClap, IntoArgs, Debug, Clone)]
@@ -80,8 +99,56 @@ pub struct Install {
     pub interactive: bool,
     #[clap(long)]
     pub nightly: bool,
+    /// Version to install, repeat to install several versions at once,
+    /// their packages are downloaded concurrently (up to
+    /// `EDGEDB_DOWNLOAD_JOBS`, default 4)
     #[clap(long, conflicts_with="nightly")]
-    pub version: Option<ver::Filter>,
+    pub version: Vec<ver::Filter>,
+    /// Install the newest version that is at least this one,
+    /// e.g. `--at-least=2.0`
+    #[clap(long, conflicts_with="version")]
+    pub at_least: Option<ver::Specific>,
+    /// Keep the downloaded package in the cache directory after installation
+    /// and print its path, the package is downloaded even if this version
+    /// is already installed
+    #[clap(long)]
+    pub keep_download: bool,
+    /// Resolve the package and show what would be installed, without
+    /// downloading anything
+    #[clap(long)]
+    pub dry_run: bool,
+    /// Output dry-run result in JSON format
+    #[clap(long, requires="dry_run")]
+    pub json: bool,
+    /// Limit download speed in bytes per second, e.g. `500K` or `2M`
+    #[clap(long, parse(try_from_str=parse_size))]
+    pub limit_rate: Option<u64>,
+    /// Never access the network, use only indexes and packages
+    /// from the `EDGEDB_PKG_CACHE` directory
+    #[clap(long)]
+    pub offline: bool,
+    /// Do not show download progress (also `EDGEDB_NO_PROGRESS=1`)
+    #[clap(long)]
+    pub no_progress: bool,
+    /// Check PGP signature of the package against the EdgeDB release key
+    /// read from the file in `EDGEDB_RELEASE_KEY` (requires `gpg`)
+    #[clap(long)]
+    pub verify_signature: bool,
+}
+
+#[derive(EdbClap, IntoArgs, Debug, Clone)]
+pub struct InstallFromUrl {
+    /// URL of the `.tar.zst` or `.zip` package
+    pub url: url::Url,
+    /// Version contained in the package, e.g. `2.0-dev.6543+1234567`
+    #[clap(long)]
+    pub version: ver::Build,
+    /// Expected blake2b hash of the package in hex
+    #[clap(long, required_unless_present="no_verify")]
+    pub blake2b: Option<String>,
+    /// Install the package without verifying its hash
+    #[clap(long, conflicts_with="blake2b")]
+    pub no_verify: bool,
 }
 
 #[derive(EdbClap, IntoArgs, Debug, Clone)]
@@ -119,6 +186,70 @@ pub struct ListVersions {
     pub json: bool,
 }
 
+#[derive(EdbClap, IntoArgs, Debug, Clone)]
+pub struct ListCache {
+    /// Re-hash packages and report whether they match their file names
+    #[clap(long)]
+    pub verify: bool,
+    /// Number of files to verify in parallel (default is number of CPUs)
+    #[clap(short='j', long, validator(jobs_opt), requires="verify")]
+    pub jobs: Option<usize>,
+    /// Output in JSON format
+    #[clap(long)]
+    pub json: bool,
+}
+
+#[derive(EdbClap, IntoArgs, Debug, Clone)]
+pub struct VerifyCache {
+    /// Number of files to verify in parallel (default is number of CPUs).
+    /// Use lower values to reduce disk load.
+    #[clap(short='j', long, validator(jobs_opt))]
+    pub jobs: Option<usize>,
+}
+
+#[derive(EdbClap, IntoArgs, Debug, Clone)]
+pub struct CachePath {
+    #[clap(long)]
+    pub nightly: bool,
+    #[clap(long, conflicts_with="nightly")]
+    pub version: Option<ver::Filter>,
+    /// Output in JSON format
+    #[clap(long)]
+    pub json: bool,
+}
+
+#[derive(EdbClap, Debug, Clone)]
+pub struct CheckPackage {
+    /// Path to the downloaded package file
+    #[clap(value_hint=ValueHint::FilePath)]
+    pub path: PathBuf,
+    #[clap(long)]
+    pub nightly: bool,
+    #[clap(long, conflicts_with="nightly")]
+    pub version: Option<ver::Filter>,
+    /// Output in JSON format
+    #[clap(long)]
+    pub json: bool,
+}
+
+#[derive(EdbClap, IntoArgs, Debug, Clone)]
+pub struct VerifyInstall {
+    /// Installed version to verify
+    #[clap(long)]
+    pub version: ver::Specific,
+    /// Re-extract the package if installed files differ from it
+    #[clap(long)]
+    pub repair: bool,
+}
+
+#[derive(EdbClap, IntoArgs, Debug, Clone)]
+pub struct ResolveQuery {
+    #[clap(long)]
+    pub nightly: bool,
+    #[clap(long, conflicts_with="nightly")]
+    pub version: Option<ver::Filter>,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum StartConf {
     Auto,
@@ -160,6 +291,26 @@ pub struct Create {
     #[clap(long, hide=true)]
     pub cloud_org: Option<String>,
 
+    /// Maximum number of connections of the EdgeDB Cloud instance.
+    /// Note: the limit may be further constrained by the instance tier
+    #[clap(long, hide=true, requires="cloud")]
+    #[clap(validator(max_connections_opt))]
+    pub max_connections: Option<u16>,
+
+    /// Seconds to wait for the EdgeDB Cloud instance to become ready
+    /// (default is 300, or `EDGEDB_CLOUD_CREATE_TIMEOUT`)
+    #[clap(long, hide=true, requires="cloud")]
+    pub timeout: Option<u64>,
+
+    /// Print the created EdgeDB Cloud instance in JSON format
+    #[clap(long, hide=true, requires="cloud")]
+    pub json: bool,
+
+    /// Succeed if EdgeDB Cloud instance with this name already exists
+    /// (waits for it if it is still being created)
+    #[clap(long, hide=true, requires="cloud")]
+    pub if_not_exists: bool,
+
     /// Do not ask questions, assume user wants to delete instance
     #[clap(long)]
     pub non_interactive: bool,
@@ -193,6 +344,11 @@ pub struct Destroy {
     /// Do not ask questions, assume user wants to delete instance
     #[clap(long)]
     pub non_interactive: bool,
+
+    /// Show what would be deleted (including the EdgeDB Cloud instance and
+    /// the credentials file) without deleting anything
+    #[clap(long)]
+    pub dry_run: bool,
 }
 
 #[derive(EdbClap, Clone, Debug)]
@@ -336,6 +492,36 @@ pub struct List {
     /// List EdgeDB Cloud instances
     #[clap(long, hide=true)]
     pub cloud: bool,
+
+    /// List EdgeDB Cloud instances of every logged in profile
+    #[clap(long, hide=true, requires="cloud")]
+    pub all_profiles: bool,
+
+    /// Output format for EdgeDB Cloud instances
+    ///
+    /// `prometheus` emits metrics in the Prometheus text exposition format,
+    /// suitable for a textfile collector:
+    /// `edgedb_cloud_instance_status{org,name,status}` is 1 for every
+    /// instance and `edgedb_cloud_instances{status}` is the number of
+    /// instances in each status.
+    #[clap(long, hide=true, requires="cloud", possible_values=&["prometheus"])]
+    #[clap(conflicts_with_all=&["extended", "debug", "json"])]
+    pub format: Option<String>,
+
+    /// Show only EdgeDB Cloud instances of the organization with this slug
+    #[clap(long, hide=true, requires="cloud")]
+    pub org: Option<String>,
+
+    /// Show only EdgeDB Cloud instances with this status
+    /// (e.g. `creating` or `available`)
+    #[clap(long, hide=true, requires="cloud")]
+    pub status: Option<String>,
+
+    /// Print number of EdgeDB Cloud instances by status and organization
+    /// instead of the full list
+    #[clap(long, hide=true, requires="cloud")]
+    #[clap(conflicts_with_all=&["extended", "debug", "format"])]
+    pub summary: bool,
 }
 
 #[derive(EdbClap, IntoArgs, Debug, Clone)]
@@ -434,6 +620,15 @@ pub struct Upgrade {
     /// This is used by `project upgrade --force`
     #[clap(long, hide=true)]
     pub force_dump_restore: bool,
+
+    /// Resolve the package the instance would be upgraded to and show it,
+    /// without downloading anything
+    #[clap(long)]
+    pub dry_run: bool,
+
+    /// Output dry-run result in JSON format
+    #[clap(long, requires="dry_run")]
+    pub json: bool,
 }
 
 #[derive(EdbClap, IntoArgs, Debug, Clone)]
@@ -500,6 +695,22 @@ pub struct Info {
     /// Output in JSON format
     #[clap(long)]
     pub json: bool,
+    /// Output format: `json` or `yaml` (`--bin-path` is printed as is
+    /// in YAML mode)
+    #[clap(long, possible_values=&["json", "yaml"][..])]
+    #[clap(conflicts_with="json")]
+    pub format: Option<String>,
+    /// Show every installed version matching the filter rather than
+    /// the latest one
+    #[clap(long)]
+    pub all: bool,
+    /// Show whether instances using the version are running
+    #[clap(long)]
+    pub status: bool,
+    /// Show size of data directories of instances using the version
+    /// (walks the directories, so it may be slow)
+    #[clap(long)]
+    pub data_size: bool,
 
     #[clap(long)]
     pub latest: bool,
@@ -519,6 +730,24 @@ pub struct ShowCredentials {
     pub insecure_dsn: bool,
 }
 
+#[derive(EdbClap, IntoArgs, Debug, Clone)]
+pub struct Ping {
+    /// Name of the instance
+    #[clap(validator(instance_name_opt), hide=true)]
+    #[clap(value_hint=ValueHint::Other)]  // TODO complete instance name
+    pub name: Option<String>,
+
+    /// Name of the instance
+    #[clap(short='I', long)]
+    #[clap(validator(instance_name_opt))]
+    #[clap(value_hint=ValueHint::Other)]  // TODO complete instance name
+    pub instance: Option<String>,
+
+    /// Output in JSON format
+    #[clap(long)]
+    pub json: bool,
+}
+
 impl FromStr for StartConf {
     type Err = anyhow::Error;
     fn from_str(s: &str) -> anyhow::Result<StartConf> {
@@ -560,6 +789,20 @@ pub fn instance_name_opt(name: &str) -> Result<(), String> {
                 (regex: ^[a-zA-Z_][a-zA-Z_0-9]*$)".into())
 }
 
+fn jobs_opt(jobs: &str) -> Result<(), String> {
+    match jobs.parse::<usize>() {
+        Ok(jobs) if jobs > 0 => Ok(()),
+        _ => Err("number of jobs must be a positive integer".into()),
+    }
+}
+
+fn max_connections_opt(value: &str) -> Result<(), String> {
+    match value.parse::<u16>() {
+        Ok(num) if (1..=10000).contains(&num) => Ok(()),
+        _ => Err("maximum connections must be between 1 and 10000".into()),
+    }
+}
+
 pub fn instance_arg<'x>(positional: &'x Option<String>,
                         named: &'x Option<String>)
     -> anyhow::Result<&'x str>
@@ -0,0 +1,83 @@
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use async_std::task;
+use edgedb_client::Builder;
+use humantime::format_duration;
+
+use crate::portable::options::{Ping, instance_arg};
+use crate::print::{self, Highlight};
+use crate::table;
+
+
+#[derive(Debug, thiserror::Error)]
+pub enum PingError {
+    #[error("cannot read credentials of instance {0:?}")]
+    Credentials(String),
+    #[error("cannot connect to instance {0:?}")]
+    Connect(String),
+    #[error("test query failed on instance {0:?}")]
+    Query(String),
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all="kebab-case")]
+struct JsonPing<'a> {
+    instance: &'a str,
+    server_version: &'a str,
+    connect_ms: u128,
+    query_ms: u128,
+}
+
+struct PingResult {
+    server_version: String,
+    connect_time: Duration,
+    query_time: Duration,
+}
+
+async fn do_ping(name: &str) -> anyhow::Result<PingResult> {
+    let mut builder = Builder::uninitialized();
+    builder.read_instance(name).await
+        .with_context(|| PingError::Credentials(name.into()))?;
+    let start = Instant::now();
+    let config = builder.build()
+        .with_context(|| PingError::Credentials(name.into()))?;
+    let mut conn = config.connect().await
+        .with_context(|| PingError::Connect(name.into()))?;
+    let connect_time = start.elapsed();
+    let start = Instant::now();
+    conn.query_row::<i64, _>("SELECT 1", &()).await
+        .with_context(|| PingError::Query(name.into()))?;
+    let query_time = start.elapsed();
+    let server_version = conn.query_row::<String, _>(
+        "SELECT sys::get_version_as_str()", &()).await
+        .with_context(|| PingError::Query(name.into()))?;
+    Ok(PingResult { server_version, connect_time, query_time })
+}
+
+pub fn ping(cmd: &Ping) -> anyhow::Result<()> {
+    let name = instance_arg(&cmd.name, &cmd.instance)?;
+    let result = task::block_on(do_ping(name))?;
+    if cmd.json {
+        println!("{}", serde_json::to_string_pretty(&JsonPing {
+            instance: name,
+            server_version: &result.server_version,
+            connect_ms: result.connect_time.as_millis(),
+            query_ms: result.query_time.as_millis(),
+        })?);
+    } else {
+        print::success_msg("Instance is reachable", name.emphasize());
+        table::settings(&[
+            ("Server version", &result.server_version),
+            ("Connect time", &format_duration(
+                truncate_to_millis(result.connect_time)).to_string()),
+            ("Query time", &format_duration(
+                truncate_to_millis(result.query_time)).to_string()),
+        ]);
+    }
+    Ok(())
+}
+
+fn truncate_to_millis(dur: Duration) -> Duration {
+    Duration::from_millis(dur.as_millis() as u64)
+}
@@ -2,8 +2,20 @@ use std::fs;
 use std::env;
 
 use anyhow::Context;
+use once_cell::sync::OnceCell;
+
+static CLI_PLATFORM: OnceCell<&'static str> = OnceCell::new();
+static SERVER_PLATFORM: OnceCell<&'static str> = OnceCell::new();
 
 pub fn get_cli() -> anyhow::Result<&'static str> {
+    CLI_PLATFORM.get_or_try_init(detect_cli).map(|x| *x)
+}
+
+pub fn get_server() -> anyhow::Result<&'static str> {
+    SERVER_PLATFORM.get_or_try_init(detect_server).map(|x| *x)
+}
+
+fn detect_cli() -> anyhow::Result<&'static str> {
     if cfg!(target_arch="x86_64") {
         if cfg!(target_os="macos") {
             return Ok("x86_64-apple-darwin");
@@ -25,7 +37,7 @@ pub fn get_cli() -> anyhow::Result<&'static str> {
     }
 }
 
-pub fn get_server() -> anyhow::Result<&'static str> {
+fn detect_server() -> anyhow::Result<&'static str> {
     if cfg!(target_arch="x86_64") {
         if cfg!(target_os="macos") {
             return Ok("x86_64-apple-darwin");
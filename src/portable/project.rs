@@ -507,14 +507,15 @@ pub fn init_existing(options: &Init, project_dir: &Path, cloud_options: &crate::
             (&format!("Schema dir {}",
                       if schema_files { "(non-empty)" } else { "(empty)" }),
              &schema_dir_path.display().to_string()),
-            // ("Version", &format!("{:?}", version)),
+            ("Version", &ver_query.display().to_string()),
             ("Instance name", &name),
         ]);
         if !schema_files {
             write_schema_default(&schema_dir)?;
         }
 
-        do_cloud_init(name, org, &stash_dir, &project_dir, &schema_dir, options, &client)  // , version)
+        do_cloud_init(name, org, &stash_dir, &project_dir, &schema_dir,
+                      &ver_query, options, &client)
     } else {
         let pkg = repository::get_server_package(&ver_query)?
             .with_context(||
@@ -563,20 +564,27 @@ fn do_init(name: &str, pkg: &PackageInfo,
             default_user: "edgedb".into(),
             cloud: false,
             cloud_org: None,
+            max_connections: None,
+            timeout: None,
+            json: false,
+            if_not_exists: false,
             non_interactive: true,
         }, name, port, &paths)?;
         create::create_service(&InstanceInfo {
             name: name.into(),
             installation: None,
             port,
+            query: None,
         })?;
         InstanceKind::Wsl(WslInfo {})
     } else {
         let inst = install::package(&pkg).context("error installing EdgeDB")?;
+        // version is controlled by `edgedb.toml` for project instances
         let info = InstanceInfo {
             name: name.into(),
             installation: Some(inst),
             port,
+            query: None,
         };
         create::bootstrap(&paths, &info, "edgedb", "edgedb")?;
         match create::create_service(&info) {
@@ -621,19 +629,26 @@ fn do_cloud_init(
     stash_dir: &Path,
     project_dir: &Path,
     schema_dir: &Path,
+    version: &Query,
     options: &Init,
     client: &CloudClient,
-    // version: String,
 ) -> anyhow::Result<ProjectInfo> {
+    // server picks the default version unless a specific one is configured
+    let version = if *version == Query::latest_stable() {
+        None
+    } else {
+        Some(version.display().to_string())
+    };
     let instance = crate::cloud::ops::CloudInstanceCreate {
         name: name.clone(),
         org,
-        // version: Some(version),
-        // default_database: None,
-        // default_user: None,
+        max_connections: None,
+        version,
+        default_database: None,
+        default_user: None,
     };
     task::block_on(
-        crate::cloud::ops::create_cloud_instance(client, &instance)
+        crate::cloud::ops::create_cloud_instance(client, &instance, None)
     )?;
     write_stash_dir(stash_dir, project_dir, &name)?;
     if !options.no_migrations {
@@ -743,7 +758,8 @@ pub fn init_new(options: &Init, project_dir: &Path, opts: &crate::options::Optio
             write_schema_default(&schema_dir_path)?;
         }
 
-        do_cloud_init(name, org, &stash_dir, &project_dir, &schema_dir, options, &client)  // , version)
+        do_cloud_init(name, org, &stash_dir, &project_dir, &schema_dir,
+                      &ver_query, options, &client)
     } else {
         let pkg = ask_version(options)?;
 
@@ -1060,7 +1076,8 @@ fn write_config(path: &Path, version: &Query) -> anyhow::Result<()> {
 }
 
 fn ask_version(options: &Init) -> anyhow::Result<PackageInfo> {
-    let ver_query = options.server_version.clone().unwrap_or(Query::stable());
+    let ver_query = options.server_version.clone()
+        .unwrap_or_else(Query::latest_stable);
     if options.non_interactive || options.server_version.is_some() {
         let pkg = repository::get_server_package(&ver_query)?
             .with_context(|| format!("no package matching {} found",
@@ -1081,7 +1098,7 @@ fn ask_version(options: &Init) -> anyhow::Result<PackageInfo> {
         let value = q.ask()?;
         let value = value.trim();
         if value == "nightly" {
-            match repository::get_server_package(&Query::nightly()) {
+            match repository::get_server_package(&Query::latest_nightly()) {
                 Ok(Some(pkg)) => return Ok(pkg),
                 Ok(None) => {
                     print::error("No nightly versions found");
@@ -1117,7 +1134,9 @@ fn ask_version(options: &Init) -> anyhow::Result<PackageInfo> {
 
 fn ask_cloud_version(options: &Init) -> anyhow::Result<String> {
     if options.non_interactive {
-        Ok("*".into())
+        Ok(options.server_version.as_ref()
+            .map(|ver| ver.display().to_string())
+            .unwrap_or_else(|| "*".into()))
     } else {
         let mut q = question::String::new(
             "Specify the version of EdgeDB to use with this project"
@@ -1132,7 +1151,7 @@ fn ask_cloud_version(options: &Init) -> anyhow::Result<String> {
 }
 
 async fn ask_cloud_org(client: &CloudClient, options: &Init) -> anyhow::Result<String> {
-    let orgs: Vec<crate::cloud::ops::Org> = client.get("orgs/").await?;
+    let orgs: Vec<crate::cloud::ops::Org> = client.get_all("orgs/").await?;
     if options.non_interactive {
         Ok(orgs.into_iter().next().unwrap().id)
     } else {
@@ -1418,6 +1437,8 @@ pub fn update_toml(options: &Upgrade) -> anyhow::Result<()> {
                     verbose: false,
                     force: options.force,
                     force_dump_restore: options.force,
+                    dry_run: false,
+                    json: false,
                 })?;
             } else {
                 // When force is used we might upgrade to the same version, but
@@ -1522,6 +1543,8 @@ pub fn upgrade_instance(options: &Upgrade) -> anyhow::Result<()> {
                 verbose: false,
                 force: options.force,
                 force_dump_restore: options.force,
+                dry_run: false,
+                json: false,
             })?;
         } else {
             // When force is used we might upgrade to the same version, but
@@ -1538,7 +1561,8 @@ pub fn upgrade_instance(options: &Upgrade) -> anyhow::Result<()> {
         echo!("EdgeDB instance is up to date with \
                the specification in the `edgedb.toml`.");
         if cfg_ver.channel != Channel::Nightly {
-            if let Some(pkg) =repository::get_server_package(&Query::stable())?
+            if let Some(pkg) =
+                repository::get_server_package(&Query::latest_stable())?
             {
                 echo!("New major version is available:",
                       pkg.version.emphasize());
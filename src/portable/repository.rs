@@ -1,44 +1,185 @@
 use std::cmp::min;
+use std::convert::TryInto;
+use std::collections::{HashMap, VecDeque};
 use std::env;
 use std::fmt;
+use std::io::{self, SeekFrom};
 use std::iter;
-use std::time::Duration;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, SystemTime};
 
 use anyhow::Context;
+use async_std::channel;
 use async_std::fs;
-use async_std::io::{ReadExt, WriteExt};
-use async_std::path::Path;
+use async_std::io::{self as async_io, ReadExt, WriteExt, SeekExt};
+use async_std::path::{Path, PathBuf};
 use async_std::task;
 use async_std::prelude::FutureExt;
 use fn_error_context::context;
-use indicatif::{ProgressBar, ProgressStyle};
+use futures_util::stream::{self, StreamExt};
+use indicatif::{HumanDuration, MultiProgress, ProgressBar, ProgressStyle};
 use once_cell::sync::OnceCell;
 use serde::{ser, de, Serialize, Deserialize};
 use url::Url;
 
+use crate::interrupt::Interrupt;
+use crate::platform::tmp_file_path;
+use crate::portable::hash::{self, HashKind, Hasher};
 use crate::portable::platform;
+use crate::portable::proxy::{ProxyClient, ProxyConfig};
 use crate::portable::ver;
 use crate::portable::windows;
 
 
-const MAX_ATTEMPTS: u32 = 10;
+const DEFAULT_MAX_ATTEMPTS: u32 = 10;
+const MAX_ATTEMPTS_LIMIT: u32 = 100;
+const DEFAULT_RETRY_BASE: u64 = 5;
+const MAX_RETRY_BASE: u64 = 60;
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(300);
+const DEFAULT_DOWNLOAD_JOBS: usize = 4;
+const MAX_DOWNLOAD_JOBS: usize = 16;
+const DEFAULT_BUFFER_SIZE: usize = 16384;
+const MIN_BUFFER_SIZE: usize = 1024;
+const MAX_BUFFER_SIZE: usize = 4 << 20;
+const DEFAULT_MAX_INDEX_SIZE: u64 = 8 << 20;
+const PARSE_CHUNK_SIZE: usize = 65536;
+/// Parsing indexes smaller than this is too fast to show progress for
+const PARSE_PROGRESS_MIN_SIZE: u64 = 1 << 20;
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Stream that doesn't receive any data for this long is considered stalled
+const DEFAULT_STALL_TIMEOUT: Duration = Duration::from_secs(30);
+const SEGMENT_STATE_SAVE_INTERVAL: Duration = Duration::from_secs(1);
+const INDEX_CACHE_MAX_AGE: Duration = Duration::from_secs(60);
+/// Download rate for the ETA is averaged over this period
+const ETA_WINDOW: Duration = Duration::from_secs(5);
+const ETA_MIN_SPAN: Duration = Duration::from_millis(500);
+const ETA_REFRESH_INTERVAL: Duration = Duration::from_millis(500);
 pub const USER_AGENT: &str = "edgedb";
-static PKG_ROOT: OnceCell<Url> = OnceCell::new();
+static PKG_ROOTS: OnceCell<Vec<Url>> = OnceCell::new();
+static BUFFER_SIZE: OnceCell<usize> = OnceCell::new();
+static SEGMENTS: OnceCell<u64> = OnceCell::new();
+static HASH_PREFERENCE: OnceCell<Vec<HashKind>> = OnceCell::new();
+static MAX_INDEX_SIZE: OnceCell<u64> = OnceCell::new();
+static MIN_PACKAGE_COUNT: OnceCell<Option<usize>> = OnceCell::new();
+static RATE_LIMIT: OnceCell<Option<RateLimiter>> = OnceCell::new();
+static CONNECT_TIMEOUT: OnceCell<Duration> = OnceCell::new();
+static READ_TIMEOUT: OnceCell<Duration> = OnceCell::new();
+static RETRY_CONFIG: OnceCell<RetryConfig> = OnceCell::new();
+static HTTP_CLIENT: OnceCell<surf::Client> = OnceCell::new();
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+static NO_PROGRESS: AtomicBool = AtomicBool::new(false);
+static DOWNLOAD_JOBS: OnceCell<usize> = OnceCell::new();
+static SERVER_PACKAGES: OnceCell<Mutex<HashMap<(Channel, String), MemoEntry>>>
+    = OnceCell::new();
+
+#[derive(Debug, Clone, Copy)]
+struct RetryConfig {
+    max_attempts: u32,
+    /// First retry delay in seconds, subsequent ones are multiples of it
+    base: u64,
+}
+
+/// Summary of a finished download
+#[derive(Debug, Clone, Copy)]
+pub struct DownloadStats {
+    /// Size of the downloaded file
+    pub bytes: u64,
+    pub elapsed: Duration,
+}
+
+/// Server packages parsed from the index, kept for the process lifetime
+#[derive(Debug)]
+struct MemoEntry {
+    fetched: Instant,
+    packages: Vec<PackageInfo>,
+}
+
+/// Response headers along with the URL they were received from, which
+/// differs from the requested one if redirects were followed
+#[derive(Debug)]
+pub struct Fetched {
+    pub response: surf::Response,
+    pub final_url: Url,
+}
+
+/// Validators of the index saved by `write_index_cache`
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+struct IndexCacheMeta {
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    /// Unix timestamp of the last successful fetch or revalidation
+    fetched_at: u64,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct SegmentState {
+    size: u64,
+    ranges: Vec<(u64, u64)>,
+    /// Number of bytes already written for each range
+    completed: Vec<u64>,
+}
+
+enum SegmentEvent {
+    Progress(usize, u64),
+    Done(usize),
+    Failed(anyhow::Error),
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("index exceeds maximum size of {0} bytes \
+        (override with EDGEDB_MAX_INDEX_SIZE)")]
+pub struct IndexTooLarge(u64);
+
+#[derive(thiserror::Error, Debug)]
+#[error("server ignored range request (status: {status}, \
+        content-range: {content_range:?})")]
+pub struct RangeIgnored {
+    status: surf::StatusCode,
+    content_range: Option<String>,
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("{what} is {actual} bytes, expected {expected} bytes \
+        (the mirror may serve a wrong or truncated file)")]
+pub struct SizeMismatch {
+    what: &'static str,
+    expected: u64,
+    actual: u64,
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("index {url} has {count} server packages, expected at least {min} \
+        (the mirror may be partially synced)")]
+pub struct IncompleteIndex {
+    url: Url,
+    count: usize,
+    min: usize,
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("cancelled")]
+pub struct Cancelled;
 
 #[derive(thiserror::Error, Debug)]
-#[error("page not found")]
-pub struct NotFound;
+#[error("{url} not found (HTTP 404)")]
+pub struct NotFound {
+    url: Url,
+}
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum Channel {
     Stable,
-    // Prerelease,  // TODO(tailhook)
+    /// Pre-releases (alpha, beta, rc) of versions after 2.x
+    Testing,
     Nightly,
 }
 
 #[derive(Debug, Clone, serde::Serialize)]
 pub enum PackageType {
     TarZst,
+    Zip,
 }
 
 #[derive(Debug, Clone, serde::Serialize)]
@@ -50,6 +191,9 @@ pub enum Compression {
 pub struct Query {
     pub channel: Channel,
     pub version: Option<ver::Filter>,
+    pub range: Option<ver::Range>,
+    /// Prefix of the build hash, i.e. the part after `+` in `2.3+abc1234`
+    pub build_hash: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -80,7 +224,9 @@ pub struct PackageData {
 #[derive(Deserialize, Debug, Clone)]
 pub struct Verification {
     size: u64,
+    blake3: Option<String>,
     blake2b: Option<String>,
+    sha256: Option<String>,
 }
 
 #[derive(Debug, Clone, serde::Serialize)]
@@ -90,6 +236,9 @@ pub struct PackageInfo {
     pub size: u64,
     pub hash: PackageHash,
     pub kind: PackageType,
+    /// Detached PGP signature of the package, if published
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub signature: Option<Url>,
 }
 
 #[derive(Debug, Clone)]
@@ -103,7 +252,9 @@ pub struct CliPackageInfo {
 
 #[derive(Debug, Clone)]
 pub enum PackageHash {
+    Blake3(Box<str>),
     Blake2b(Box<str>),
+    Sha256(Box<str>),
     Unknown(Box<str>),
 }
 
@@ -116,56 +267,761 @@ pub struct HttpError(surf::Error);
         self.0.status(), self.0.status().canonical_reason())]
 pub struct HttpFailure(surf::Response);
 
+const BLAKE3_HEX_LEN: usize = 64;
+const BLAKE2B_HEX_LEN: usize = 128;
+const SHA256_HEX_LEN: usize = 64;
+/// Hash algorithms used to verify server packages, most preferred first
+const DEFAULT_HASH_PREFERENCE: &[HashKind] = &[
+    HashKind::Blake3,
+    HashKind::Blake2b,
+    HashKind::Sha256,
+];
+/// Length of the hash prefix in cache file names, long enough to make
+/// collisions practically impossible while keeping paths short on Windows
+const CACHE_HASH_LEN: usize = 32;
+
+#[derive(Debug, thiserror::Error)]
+#[error("checksum mismatch: expected {expected} got {actual}")]
+pub struct ChecksumMismatch {
+    pub expected: String,
+    pub actual: String,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("{0} is not in the package cache and network access is disabled \
+        (make sure EDGEDB_PKG_CACHE points to the mirrored packages)")]
+pub struct NotCached(String);
+
+#[derive(Debug, thiserror::Error)]
+#[error("no available package matches {0}, \
+        make sure the build hash is correct")]
+pub struct BuildNotFound(String);
+
+/// Client errors (4xx except 404 and 429) which are never retried
+#[derive(Debug, thiserror::Error)]
+pub enum ClientError {
+    #[error("authentication required")]
+    Unauthorized,
+    #[error("access denied, URL may be expired")]
+    Forbidden,
+    #[error("request rejected: {} {}", .0, .0.canonical_reason())]
+    Other(surf::StatusCode),
+}
+
 
 impl PackageType {
     fn as_ext(&self) -> &str {
         match self {
             PackageType::TarZst => ".tar.zst",
+            PackageType::Zip => ".zip",
         }
     }
+    /// Detects package type by extension of the file name in the `url`
+    pub fn from_url(url: &Url) -> Option<PackageType> {
+        let path = url.path();
+        [PackageType::TarZst, PackageType::Zip].iter()
+            .find(|kind| path.ends_with(kind.as_ext()))
+            .cloned()
+    }
 }
 
 impl PackageInfo {
+    /// Size of the package, unknown for packages installed from URL
+    pub fn expected_size(&self) -> Option<u64> {
+        Some(self.size).filter(|&size| size > 0)
+    }
     pub fn cache_file_name(&self) -> String {
-        // TODO(tailhook) use package hash when that is available
-        let hash = self.hash.short();
+        format!("edgedb-server_{}_{}{}",
+                encode_file_component(&self.version.to_string()),
+                encode_file_component(&self.hash.file_hash()),
+                self.kind.as_ext())
+    }
+    /// File name used before `cache_file_name` included the longer hash
+    pub fn legacy_cache_file_name(&self) -> String {
         format!("edgedb-server_{}_{:7}{}",
-                self.version, hash, self.kind.as_ext())
+                encode_file_component(&self.version.to_string()),
+                encode_file_component(self.hash.short()),
+                self.kind.as_ext())
+    }
+}
+
+/// Percent-encodes everything except alphanumerics, `.` and `-`
+///
+/// This makes the component safe to use in a file name on any filesystem
+/// and it never contains `_` which separates components in the cache files.
+pub fn encode_file_component(val: &str) -> String {
+    let mut result = String::with_capacity(val.len());
+    for byte in val.bytes() {
+        match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'.' | b'-' => {
+                result.push(byte as char);
+            }
+            _ => result.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    result
+}
+
+/// Reverses `encode_file_component`, unencoded names are returned as is
+pub fn decode_file_component(val: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(val.len());
+    let mut iter = val.bytes();
+    while let Some(byte) = iter.next() {
+        if byte == b'%' {
+            let hex = [iter.next()?, iter.next()?];
+            let hex = std::str::from_utf8(&hex).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+        } else {
+            bytes.push(byte);
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Returns package root for the specified platform
+///
+/// Platform-specific root is set by `EDGEDB_PKG_ROOT_<OS>_<ARCH>`, e.g.
+/// `EDGEDB_PKG_ROOT_LINUX_X86_64` or `EDGEDB_PKG_ROOT_MACOS_AARCH64`, and
+/// takes precedence over `EDGEDB_PKG_ROOT`, which applies to all platforms.
+///
+/// When several roots are configured, the primary (first) one is returned,
+/// see `platform_pkg_roots` for the fallbacks.
+pub fn platform_pkg_root(platform: &str) -> anyhow::Result<Url> {
+    Ok(platform_pkg_roots(platform)?.swap_remove(0))
+}
+
+/// Returns package roots for the platform in the order they should be tried
+///
+/// Both `EDGEDB_PKG_ROOT` and platform-specific variables accept
+/// a comma-separated list of URLs.
+pub fn platform_pkg_roots(platform: &str) -> anyhow::Result<Vec<Url>> {
+    if let Some(var) = platform_root_var(platform) {
+        if let Ok(value) = env::var(&var) {
+            return parse_roots(&value)
+                .with_context(|| format!("{} is not a valid URL list", var));
+        }
+    }
+    Ok(pkg_roots()?.clone())
+}
+
+fn parse_roots(value: &str) -> anyhow::Result<Vec<Url>> {
+    let roots = value.split(',')
+        .map(|item| item.trim())
+        .filter(|item| !item.is_empty())
+        .map(|item| Url::parse(item)
+             .with_context(|| format!("invalid URL {:?}", item)))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    if roots.is_empty() {
+        anyhow::bail!("no URLs specified");
     }
+    Ok(roots)
 }
 
-fn pkg_root() -> anyhow::Result<&'static Url> {
-    PKG_ROOT.get_or_try_init(|| {
+fn platform_root_var(platform: &str) -> Option<String> {
+    let arch = platform.split('-').next()?;
+    let os = if platform.contains("-linux-") {
+        "LINUX"
+    } else if platform.contains("-apple-darwin") {
+        "MACOS"
+    } else if platform.contains("-windows-") {
+        "WINDOWS"
+    } else {
+        return None;
+    };
+    Some(format!("EDGEDB_PKG_ROOT_{}_{}", os, arch.to_uppercase()))
+}
+
+fn pkg_roots() -> anyhow::Result<&'static Vec<Url>> {
+    PKG_ROOTS.get_or_try_init(|| {
         let pkg_root = env::var("EDGEDB_PKG_ROOT")
             .unwrap_or_else(|_| String::from("https://packages.edgedb.com"));
-        let pkg_root = Url::parse(&pkg_root)
-            .context("Package root is a valid URL")?;
-        Ok(pkg_root)
+        parse_roots(&pkg_root)
+            .context("EDGEDB_PKG_ROOT is not a valid URL list")
+    })
+}
+
+/// Size of the read buffer used by `download`
+///
+/// Larger buffer reduces syscall overhead on fast links, smaller one helps
+/// on memory-constrained devices. Overridden by `EDGEDB_DOWNLOAD_BUFSIZE`
+/// (in bytes), which is clamped to the range of 1KiB..4MiB.
+fn buffer_size() -> usize {
+    *BUFFER_SIZE.get_or_init(|| {
+        match env::var("EDGEDB_DOWNLOAD_BUFSIZE") {
+            Ok(val) => match val.parse::<usize>() {
+                Ok(size) => size.max(MIN_BUFFER_SIZE).min(MAX_BUFFER_SIZE),
+                Err(e) => {
+                    log::warn!("Invalid EDGEDB_DOWNLOAD_BUFSIZE {:?}: {}. \
+                                Using default of {} bytes.",
+                                val, e, DEFAULT_BUFFER_SIZE);
+                    DEFAULT_BUFFER_SIZE
+                }
+            },
+            Err(_) => DEFAULT_BUFFER_SIZE,
+        }
+    })
+}
+
+/// Maximum size of the package index accepted from the server
+///
+/// Protects against a broken mirror sending unbounded data. Overridden by
+/// `EDGEDB_MAX_INDEX_SIZE` (in bytes).
+fn max_index_size() -> u64 {
+    *MAX_INDEX_SIZE.get_or_init(|| {
+        match env::var("EDGEDB_MAX_INDEX_SIZE") {
+            Ok(val) => match val.parse::<u64>() {
+                Ok(size) => size,
+                Err(e) => {
+                    log::warn!("Invalid EDGEDB_MAX_INDEX_SIZE {:?}: {}. \
+                                Using default of {} bytes.",
+                                val, e, DEFAULT_MAX_INDEX_SIZE);
+                    DEFAULT_MAX_INDEX_SIZE
+                }
+            },
+            Err(_) => DEFAULT_MAX_INDEX_SIZE,
+        }
+    })
+}
+
+/// Minimum number of server packages expected in the index
+///
+/// Set by `EDGEDB_PKG_MIN_COUNT` to detect partially synced mirrors, no
+/// check is done by default.
+fn min_package_count() -> Option<usize> {
+    *MIN_PACKAGE_COUNT.get_or_init(|| {
+        let val = env::var("EDGEDB_PKG_MIN_COUNT").ok()?;
+        match val.parse::<usize>() {
+            Ok(num) => Some(num),
+            Err(e) => {
+                log::warn!("Invalid EDGEDB_PKG_MIN_COUNT {:?}: {}. \
+                            Package count is not checked.", val, e);
+                None
+            }
+        }
     })
 }
 
-fn retry_seconds() -> impl Iterator<Item=u64> {
-    [5, 15, 30, 60].iter().cloned().chain(iter::repeat(60))
+/// Parses size with an optional binary suffix, e.g. `512K`, `2M`, `1GiB`
+pub fn parse_size(value: &str) -> anyhow::Result<u64> {
+    let value = value.trim();
+    let split = value.find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (num, suffix) = value.split_at(split);
+    let multiplier: u64 = match &suffix.to_ascii_lowercase()[..] {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1 << 10,
+        "m" | "mb" | "mib" => 1 << 20,
+        "g" | "gb" | "gib" => 1 << 30,
+        _ => anyhow::bail!("unknown size suffix {:?}", suffix),
+    };
+    let num: f64 = num.parse()
+        .with_context(|| format!("invalid size {:?}", value))?;
+    let size = (num * multiplier as f64) as u64;
+    if size == 0 {
+        anyhow::bail!("size must be positive");
+    }
+    Ok(size)
+}
+
+/// Token bucket shared by all downloads in the process
+pub struct RateLimiter {
+    rate: u64,
+    /// Available bytes (negative when in debt) and time of the last update
+    state: Mutex<(f64, Instant)>,
+}
+
+impl RateLimiter {
+    fn new(rate: u64) -> RateLimiter {
+        RateLimiter {
+            rate,
+            state: Mutex::new((rate as f64, Instant::now())),
+        }
+    }
+    async fn consume(&self, bytes: usize) {
+        let delay = {
+            let mut state = self.state.lock().unwrap();
+            let (tokens, updated) = &mut *state;
+            let now = Instant::now();
+            let refill = now.duration_since(*updated).as_secs_f64()
+                * self.rate as f64;
+            // allow bursts of at most one second worth of data
+            *tokens = (*tokens + refill).min(self.rate as f64) - bytes as f64;
+            *updated = now;
+            if *tokens < 0.0 {
+                Some(Duration::from_secs_f64(-*tokens / self.rate as f64))
+            } else {
+                None
+            }
+        };
+        if let Some(delay) = delay {
+            task::sleep(delay).await;
+        }
+    }
+}
+
+/// Estimates remaining time of the download from the rate averaged over
+/// `ETA_WINDOW`, which is much steadier than the instant rate used by
+/// indicatif on bursty connections
+struct EtaEstimator {
+    /// Time and progress bar position, oldest first
+    samples: VecDeque<(Instant, u64)>,
+    refreshed: Option<Instant>,
+}
+
+impl EtaEstimator {
+    fn new() -> EtaEstimator {
+        EtaEstimator {
+            samples: VecDeque::new(),
+            refreshed: None,
+        }
+    }
+    fn record(&mut self, now: Instant, position: u64) {
+        self.samples.push_back((now, position));
+        while self.samples.len() > 2 &&
+            now.duration_since(self.samples[0].0) > ETA_WINDOW
+        {
+            self.samples.pop_front();
+        }
+    }
+    /// Bytes per second, `None` until enough time passed to tell
+    fn rate(&self) -> Option<f64> {
+        let (first_time, first_pos) = self.samples.front()?;
+        let (last_time, last_pos) = self.samples.back()?;
+        let span = last_time.duration_since(*first_time);
+        if span < ETA_MIN_SPAN {
+            return None;
+        }
+        Some(last_pos.saturating_sub(*first_pos) as f64 / span.as_secs_f64())
+    }
+    fn eta(&self, remaining: u64) -> Option<Duration> {
+        self.rate()
+            .filter(|&rate| rate > 0.0)
+            .map(|rate| Duration::from_secs_f64(remaining as f64 / rate))
+    }
+    /// Records the current position of the `bar` and updates ETA shown
+    /// in its message at most every `ETA_REFRESH_INTERVAL`
+    fn tick(&mut self, bar: &ProgressBar) {
+        let now = Instant::now();
+        let position = bar.position();
+        self.record(now, position);
+        if let Some(refreshed) = self.refreshed {
+            if now.duration_since(refreshed) < ETA_REFRESH_INTERVAL {
+                return;
+            }
+        }
+        self.refreshed = Some(now);
+        let remaining = bar.length().saturating_sub(position);
+        match self.eta(remaining) {
+            Some(eta) => bar.set_message(HumanDuration(eta).to_string()),
+            None => bar.set_message("-"),
+        }
+    }
+}
+
+/// Download speed limit
+///
+/// Set by `--limit-rate`, `EDGEDB_DOWNLOAD_RATE_LIMIT` or
+/// `EDGEDB_PKG_LIMIT_RATE` (e.g. `2MiB`), unlimited by default.
+fn rate_limiter() -> Option<&'static RateLimiter> {
+    RATE_LIMIT.get_or_init(|| {
+        let (name, val) = ["EDGEDB_DOWNLOAD_RATE_LIMIT", "EDGEDB_PKG_LIMIT_RATE"]
+            .iter()
+            .find_map(|name| env::var(name).ok().map(|val| (name, val)))?;
+        match parse_size(&val) {
+            Ok(rate) => Some(RateLimiter::new(rate)),
+            Err(e) => {
+                log::warn!("Invalid {} {:?}: {:#}. \
+                            Download speed is not limited.", name, val, e);
+                None
+            }
+        }
+    }).as_ref()
+}
+
+/// Limits download speed of this process, must be called before downloading
+pub fn set_rate_limit(rate: u64) {
+    if RATE_LIMIT.set(Some(RateLimiter::new(rate))).is_err() {
+        log::warn!("Download rate limit is already set");
+    }
+}
+
+/// Disables network access, only `EDGEDB_PKG_CACHE` is used
+pub fn set_offline(offline: bool) {
+    OFFLINE.store(offline, Ordering::SeqCst);
+}
+
+fn is_offline() -> bool {
+    OFFLINE.load(Ordering::SeqCst)
+}
+
+/// Disables download progress bars, same as setting `EDGEDB_NO_PROGRESS`
+pub fn set_no_progress(no_progress: bool) {
+    NO_PROGRESS.store(no_progress, Ordering::SeqCst);
+}
+
+/// Whether indexes fetched earlier (in this process or by a previous
+/// invocation) can be reused, disabled by setting `EDGEDB_NO_INDEX_CACHE`
+fn use_index_cache() -> bool {
+    env::var_os("EDGEDB_NO_INDEX_CACHE")
+        .map(|val| val.is_empty() || val == "0")
+        .unwrap_or(true)
+}
+
+/// Progress bars are only drawn on a terminal, otherwise they produce
+/// escape codes in logs
+fn show_progress() -> bool {
+    !NO_PROGRESS.load(Ordering::SeqCst) &&
+        env::var_os("EDGEDB_NO_PROGRESS")
+            .map(|val| val.is_empty() || val == "0")
+            .unwrap_or(true) &&
+        atty::is(atty::Stream::Stderr)
+}
+
+/// Local directory with mirrored indexes and packages
+///
+/// Set by `EDGEDB_PKG_CACHE`. Indexes are looked up by their file name
+/// (e.g. `x86_64-unknown-linux-gnu.json`) and packages by
+/// `PackageInfo::cache_file_name`.
+pub fn pkg_cache_dir() -> Option<PathBuf> {
+    env::var_os("EDGEDB_PKG_CACHE")
+        .filter(|val| !val.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Returns a path to the file in the package cache if it exists there
+async fn pkg_cache_file(name: &str) -> Option<PathBuf> {
+    let path = pkg_cache_dir()?.join(name);
+    if path.exists().await {
+        Some(path)
+    } else {
+        log::debug!("{:?} is not in the package cache", path);
+        None
+    }
+}
+
+fn url_file_name(url: &Url) -> &str {
+    url.path_segments().and_then(|s| s.last()).unwrap_or("")
+}
+
+async fn get_cached_json<T>(url: &Url, progress: &mut dyn FnMut(u64, u64),
+                            cancel: &AtomicBool)
+    -> anyhow::Result<T>
+    where T: serde::de::DeserializeOwned,
+{
+    match pkg_cache_file(url_file_name(url)).await {
+        Some(path) => {
+            log::info!("Using cached index {:?}", path);
+            let data = fs::read(&path).await
+                .with_context(|| format!("cannot read {:?}", path))?;
+            parse_json(&data, progress, cancel)
+                .with_context(|| format!("cannot decode {:?}", path))
+        }
+        None => get_json_with(url, progress, cancel).await,
+    }
+}
+
+/// Copies the package from the package cache if its hash matches
+async fn copy_from_pkg_cache(dest: &Path, expected: &PackageHash)
+    -> anyhow::Result<bool>
+{
+    let name = dest.file_name().and_then(|n| n.to_str()).unwrap_or("");
+    let src = match pkg_cache_file(name).await {
+        Some(src) => src,
+        None => return Ok(false),
+    };
+    fs::copy(&src, dest).await
+        .with_context(|| format!("cannot copy {:?} -> {:?}", src, dest))?;
+    let digest = hash::hash_file_async(dest.as_ref(), expected.kind()).await?;
+    match verify_download(dest, &digest, expected).await {
+        Ok(()) => {
+            log::info!("Using cached package {:?}", src);
+            Ok(true)
+        }
+        Err(e) => {
+            log::warn!("Cached package {:?} is invalid: {:#}", src, e);
+            Ok(false)
+        }
+    }
+}
+
+async fn throttle(bytes: usize) {
+    if let Some(limiter) = rate_limiter() {
+        limiter.consume(bytes).await;
+    }
+}
+
+/// Number of concurrent range requests used to download a package
+///
+/// Set by `EDGEDB_DOWNLOAD_SEGMENTS`, default is a single request, i.e.
+/// segmented download is disabled.
+pub fn download_segments() -> u64 {
+    *SEGMENTS.get_or_init(|| {
+        match env::var("EDGEDB_DOWNLOAD_SEGMENTS") {
+            Ok(val) => match val.parse::<u64>() {
+                Ok(num) => num.max(1).min(16),
+                Err(e) => {
+                    log::warn!("Invalid EDGEDB_DOWNLOAD_SEGMENTS {:?}: {}. \
+                                Segmented download is disabled.", val, e);
+                    1
+                }
+            },
+            Err(_) => 1,
+        }
+    })
+}
+
+/// Order in which hashes of server packages are tried, most preferred first
+///
+/// Set by `EDGEDB_HASH_PREFERENCE` as a comma-separated list of algorithms,
+/// e.g. `sha256,blake2b`. Algorithms that are not listed are never used.
+fn hash_preference() -> &'static [HashKind] {
+    HASH_PREFERENCE.get_or_init(|| {
+        match env::var("EDGEDB_HASH_PREFERENCE") {
+            Ok(val) => match parse_hash_preference(&val) {
+                Ok(kinds) => kinds,
+                Err(e) => {
+                    log::warn!("Invalid EDGEDB_HASH_PREFERENCE {:?}: {:#}. \
+                                Using default order.", val, e);
+                    DEFAULT_HASH_PREFERENCE.to_vec()
+                }
+            },
+            Err(_) => DEFAULT_HASH_PREFERENCE.to_vec(),
+        }
+    })
+}
+
+fn parse_hash_preference(val: &str) -> anyhow::Result<Vec<HashKind>> {
+    let kinds = val.split(',')
+        .map(|item| item.trim())
+        .filter(|item| !item.is_empty())
+        .map(|item| item.parse())
+        .collect::<anyhow::Result<Vec<HashKind>>>()?;
+    if kinds.is_empty() {
+        anyhow::bail!("no hash algorithms specified");
+    }
+    Ok(kinds)
+}
+
+fn env_duration(name: &str, default: Duration) -> Duration {
+    match env::var(name) {
+        Ok(val) => match val.parse::<u64>() {
+            Ok(secs) => Duration::from_secs(secs),
+            Err(e) => {
+                log::warn!("Invalid {} {:?}: {}. Using default of {} seconds.",
+                           name, val, e, default.as_secs());
+                default
+            }
+        },
+        Err(_) => default,
+    }
+}
+
+/// Client used for all requests to the package server
+///
+/// It's shared so that connections are kept alive across index fetches
+/// and package downloads. Every request carries `User-Agent` and is routed
+/// through `HTTPS_PROXY` or `HTTP_PROXY` (or lowercase variants) unless
+/// the host is excluded by `NO_PROXY`. Timeouts are set per request because
+/// they are retried differently from other errors.
+fn http_client() -> anyhow::Result<&'static surf::Client> {
+    HTTP_CLIENT.get_or_try_init(|| {
+        let config = surf::Config::new()
+            .add_header("User-Agent", USER_AGENT)
+            .map_err(|e| e.into_inner())?
+            .set_http_keep_alive(true);
+        let config = match ProxyConfig::from_env() {
+            Some(proxy) => {
+                log::debug!("Using proxy: {:?}", proxy);
+                config.set_http_client(ProxyClient::new(proxy))
+            }
+            None => config,
+        };
+        Ok(config.try_into()?)
+    })
+}
+
+/// Time to wait for the server to respond with headers
+///
+/// Fails fast if the mirror is unreachable, such timeouts are retried.
+/// Overridden by `EDGEDB_CONNECT_TIMEOUT` (in seconds).
+fn connect_timeout() -> Duration {
+    *CONNECT_TIMEOUT.get_or_init(|| {
+        env_duration("EDGEDB_CONNECT_TIMEOUT", DEFAULT_CONNECT_TIMEOUT)
+    })
+}
+
+/// Maximum time a single read of the response body may stall
+///
+/// Unlike overall timeout, this tolerates slow downloads as long as data
+/// keeps flowing. Overridden by `EDGEDB_READ_TIMEOUT` or
+/// `EDGEDB_HTTP_TIMEOUT` (in seconds).
+fn read_timeout() -> Duration {
+    *READ_TIMEOUT.get_or_init(|| {
+        let name = if env::var_os("EDGEDB_READ_TIMEOUT").is_some() {
+            "EDGEDB_READ_TIMEOUT"
+        } else {
+            "EDGEDB_HTTP_TIMEOUT"
+        };
+        env_duration(name, DEFAULT_STALL_TIMEOUT)
+    })
+}
+
+async fn read_body(body: &mut surf::Body, buf: &mut [u8])
+    -> anyhow::Result<usize>
+{
+    async_io::timeout(read_timeout(), body.read(buf)).await
+        .context("error reading response body")
+}
+
+fn is_stalled(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<io::Error>()
+        .map(|e| e.kind() == io::ErrorKind::TimedOut)
+        .unwrap_or(false)
+}
+
+fn env_clamped<T>(name: &str, default: T, min: T, max: T) -> T
+    where T: std::str::FromStr + Ord + fmt::Display + Copy,
+          T::Err: fmt::Display,
+{
+    match env::var(name) {
+        Ok(val) => match val.parse::<T>() {
+            Ok(num) => num.max(min).min(max),
+            Err(e) => {
+                log::warn!("Invalid {} {:?}: {}. Using default of {}.",
+                           name, val, e, default);
+                default
+            }
+        },
+        Err(_) => default,
+    }
+}
+
+/// Number of attempts and backoff for fetching from the package server
+///
+/// Overridden by `EDGEDB_DOWNLOAD_MAX_ATTEMPTS` and
+/// `EDGEDB_DOWNLOAD_RETRY_BASE` (first delay in seconds).
+fn retry_config() -> RetryConfig {
+    *RETRY_CONFIG.get_or_init(|| {
+        let config = RetryConfig {
+            max_attempts: env_clamped("EDGEDB_DOWNLOAD_MAX_ATTEMPTS",
+                DEFAULT_MAX_ATTEMPTS, 1, MAX_ATTEMPTS_LIMIT),
+            base: env_clamped("EDGEDB_DOWNLOAD_RETRY_BASE",
+                DEFAULT_RETRY_BASE, 1, MAX_RETRY_BASE),
+        };
+        log::debug!("Download retries: {} attempts, base delay {} seconds",
+                    config.max_attempts, config.base);
+        config
+    })
+}
+
+/// Parses `Retry-After` header in either delta-seconds or HTTP-date form
+///
+/// The delay is capped, so a broken server can't make us wait forever.
+pub fn parse_retry_after(value: &str, now: SystemTime) -> Option<Duration> {
+    let value = value.trim();
+    let delay = if let Ok(secs) = value.parse::<u64>() {
+        Duration::from_secs(secs)
+    } else {
+        let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+        SystemTime::from(date).duration_since(now)
+            .unwrap_or(Duration::from_secs(0))
+    };
+    Some(delay.min(MAX_RETRY_AFTER))
+}
+
+fn retry_seconds(base: u64) -> impl Iterator<Item=u64> {
+    [1, 3, 6, 12].iter().map(move |x| x * base)
+        .chain(iter::repeat(12 * base))
 }
 
 pub async fn get_header(original_url: &Url, permanent_warning: bool)
+    -> anyhow::Result<Fetched>
+{
+    _get_header(original_url, permanent_warning, &[]).await
+}
+
+async fn get_range(url: &Url, start: u64, end: u64)
     -> anyhow::Result<surf::Response>
+{
+    let range = format!("bytes={}-{}", start, end-1);
+    let res = _get_header(url, false, &[("Range", range)]).await?.response;
+    let content_range = res.header("Content-Range")
+        .map(|val| val.last().as_str().to_string());
+    let matches = content_range.as_ref()
+        .map(|val| content_range_matches(val, start, end))
+        .unwrap_or(false);
+    if res.status() != surf::StatusCode::PartialContent || !matches {
+        return Err(RangeIgnored {
+            status: res.status(),
+            content_range,
+        }.into());
+    }
+    Ok(res)
+}
+
+/// Checks that `Content-Range` header covers exactly `start..end`
+fn content_range_matches(value: &str, start: u64, end: u64) -> bool {
+    let range = match value.trim().strip_prefix("bytes ") {
+        Some(range) => range.split('/').next().unwrap_or(""),
+        None => return false,
+    };
+    match range.split_once('-') {
+        Some((first, last)) => {
+            first.trim().parse::<u64>().ok() == Some(start) &&
+                last.trim().parse::<u64>().ok() == Some(end - 1)
+        }
+        None => false,
+    }
+}
+
+async fn _get_header(original_url: &Url, permanent_warning: bool,
+                     headers: &[(&'static str, String)])
+    -> anyhow::Result<Fetched>
 {
     use surf::StatusCode::{self, MovedPermanently, PermanentRedirect};
     use surf::StatusCode::{TooManyRequests};
 
+    if is_offline() {
+        return Err(NotCached(original_url.to_string()).into());
+    }
     let mut url = original_url.clone();
     let mut attempt = 0;
-    let mut retry = retry_seconds();
+    let config = retry_config();
+    let mut retry = retry_seconds(config.base);
 
     loop {
 
         log::info!("Fetching JSON at {}", url);
-        match surf::get(&url).header("User-Agent", USER_AGENT).await {
-            Ok(res) if res.status().is_success() => {
-                break Ok(res);
+        let mut req = http_client()?.get(&url);
+        for (name, value) in headers {
+            req = req.header(*name, &value[..]);
+        }
+        // Response future resolves as soon as headers are received, so this
+        // timeout doesn't limit the time spent downloading the body
+        match req.timeout(connect_timeout()).await {
+            Err(_) => {
+                let secs = retry.next().unwrap();
+                log::warn!("Timed out connecting to {}. \
+                            Will retry in {} seconds.", url, secs);
+                task::sleep(Duration::from_secs(secs)).await;
+            }
+            Ok(Ok(res)) if res.status().is_success() ||
+                           res.status() == StatusCode::NotModified
+            => {
+                break Ok(Fetched { response: res, final_url: url });
             }
-            Ok(res) if res.status().is_redirection() => {
+            Ok(Ok(res)) if res.status() == StatusCode::NotFound
+                => return Err(NotFound { url }.into()),
+            Ok(Ok(res)) if res.status().is_client_error() &&
+                           res.status() != TooManyRequests
+            => {
+                return Err(match res.status() {
+                    StatusCode::Unauthorized => ClientError::Unauthorized,
+                    StatusCode::Forbidden => ClientError::Forbidden,
+                    status => ClientError::Other(status),
+                }).with_context(|| format!("cannot fetch {}", url));
+            }
+            Ok(Ok(res)) if res.status().is_redirection() => {
                 let location = match res.header("Location") {
                     Some(val) => val.last().as_str(),
                     None => anyhow::bail!("unexpected redirect kind {}",
@@ -182,46 +1038,288 @@ pub async fn get_header(original_url: &Url, permanent_warning: bool)
                 if permanent_warning &&
                    matches!(res.status(), MovedPermanently | PermanentRedirect)
                 {
-                    log::warn!("Location {} permanently moved to {}.",
+                    log::warn!("Location {} permanently moved to {}. \
+                                If it's a package mirror, \
+                                update EDGEDB_PKG_ROOT.",
                                url, new_url);
                 }
                 url = new_url;
             }
-            Ok(res) if res.status().is_server_error() ||
+            Ok(Ok(res)) if res.status().is_server_error() ||
                        res.status() == TooManyRequests
             => {
-                let secs = retry.next().unwrap();
+                let delay = res.header("Retry-After")
+                    .and_then(|val| parse_retry_after(val.last().as_str(),
+                                                      SystemTime::now()))
+                    .unwrap_or_else(|| {
+                        Duration::from_secs(retry.next().unwrap())
+                    });
                 log::warn!("Error fetching {}: {}. Will retry in {} seconds.",
-                           url, res.status(), secs);
-                task::sleep(Duration::from_secs(secs)).await;
+                           url, res.status(), delay.as_secs());
+                task::sleep(delay).await;
             }
-            Ok(res) if res.status() == StatusCode::NotFound
-                => return Err(NotFound.into()),
-            Ok(res) => return Err(HttpFailure(res))?,
-            Err(e) => return Err(HttpError(e))?,
+            Ok(Ok(res)) => return Err(HttpFailure(res))?,
+            Ok(Err(e)) => return Err(HttpError(e))?,
         }
         attempt += 1;
-        if attempt > MAX_ATTEMPTS {
+        if attempt > config.max_attempts {
             anyhow::bail!("too many attempts");
         }
     }
 }
 
-async fn _get_json<T>(url: &Url) -> Result<T, anyhow::Error>
+/// Fetches the index, reusing a copy cached by previous invocations
+///
+/// The cached copy is used as is within `INDEX_CACHE_MAX_AGE`, after that
+/// it's revalidated using `If-None-Match` / `If-Modified-Since`. When
+/// `use_cache` is false the cached copy is ignored but still updated.
+async fn get_body(url: &Url, use_cache: bool) -> anyhow::Result<Vec<u8>> {
+    let cached = if use_cache { read_index_cache(url).await } else { None };
+    let mut headers = Vec::new();
+    if let Some((meta, body)) = &cached {
+        if is_fresh(meta, SystemTime::now()) {
+            log::info!("Using index {} fetched less than {} seconds ago",
+                       url, INDEX_CACHE_MAX_AGE.as_secs());
+            return Ok(body.clone());
+        }
+        if let Some(etag) = &meta.etag {
+            headers.push(("If-None-Match", etag.clone()));
+        }
+        if let Some(modified) = &meta.last_modified {
+            headers.push(("If-Modified-Since", modified.clone()));
+        }
+    }
+    let mut res = _get_header(url, true, &headers).await?.response;
+    if res.status() == surf::StatusCode::NotModified {
+        let (meta, body) = cached
+            .with_context(|| format!("unexpected {} for {}",
+                                     res.status(), url))?;
+        log::info!("Index {} is not modified, using cached copy", url);
+        let meta = IndexCacheMeta { fetched_at: unix_now(), ..meta };
+        write_index_cache(url, &meta, None).await;
+        return Ok(body);
+    }
+    let header = |name| res.header(name).map(|v| v.last().to_string());
+    let meta = IndexCacheMeta {
+        url: url.to_string(),
+        etag: header("ETag"),
+        last_modified: header("Last-Modified"),
+        fetched_at: unix_now(),
+    };
+
+    let max_size = max_index_size();
+    let body = res.take_body();
+    if body.len().map(|len| len as u64 > max_size).unwrap_or(false) {
+        return Err(IndexTooLarge(max_size).into());
+    }
+    let mut body_bytes = Vec::new();
+    body.take(max_size + 1).read_to_end(&mut body_bytes).await?;
+    if body_bytes.len() as u64 > max_size {
+        return Err(IndexTooLarge(max_size).into());
+    }
+    write_index_cache(url, &meta, Some(&body_bytes)).await;
+    Ok(body_bytes)
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn is_fresh(meta: &IndexCacheMeta, now: SystemTime) -> bool {
+    let fetched = SystemTime::UNIX_EPOCH + Duration::from_secs(meta.fetched_at);
+    now.duration_since(fetched)
+        .map(|age| age < INDEX_CACHE_MAX_AGE)
+        .unwrap_or(false)
+}
+
+/// Returns paths of the cached index body and its metadata
+fn index_cache_paths(url: &Url) -> anyhow::Result<(PathBuf, PathBuf)> {
+    let dir = PathBuf::from(crate::platform::config_dir()?.join("index-cache"));
+    let key = blake2b_simd::blake2b(url.as_str().as_bytes()).to_hex();
+    Ok((dir.join(format!("{}.json", &key[..16])),
+        dir.join(format!("{}.meta.json", &key[..16]))))
+}
+
+async fn read_index_cache(url: &Url) -> Option<(IndexCacheMeta, Vec<u8>)> {
+    let (body_path, meta_path) = index_cache_paths(url).ok()?;
+    let meta: IndexCacheMeta = serde_json::from_slice(
+        &fs::read(&meta_path).await.ok()?
+    ).map_err(|e| {
+        log::warn!("Ignoring invalid index cache {:?}: {}", meta_path, e);
+    }).ok()?;
+    if meta.url != url.as_str() {
+        return None;
+    }
+    let body = fs::read(&body_path).await.ok()?;
+    Some((meta, body))
+}
+
+/// Saves metadata and (optionally) body of the index, errors are ignored
+/// as the cache is only an optimization
+async fn write_index_cache(url: &Url, meta: &IndexCacheMeta,
+                           body: Option<&[u8]>)
+{
+    let result = async {
+        let (body_path, meta_path) = index_cache_paths(url)?;
+        if let Some(parent) = body_path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        if let Some(body) = body {
+            write_atomic(&body_path, body).await?;
+        }
+        write_atomic(&meta_path, &serde_json::to_vec(meta)?).await?;
+        Ok::<_, anyhow::Error>(())
+    }.await;
+    if let Err(e) = result {
+        log::warn!("Cannot save index cache for {}: {:#}", url, e);
+    }
+}
+
+/// Writes to a temporary file and renames it, so that concurrent
+/// invocations never read a partially written file
+async fn write_atomic(path: &Path, data: &[u8]) -> anyhow::Result<()> {
+    let tmp_path = PathBuf::from(tmp_file_path(path.as_ref()));
+    fs::write(&tmp_path, data).await?;
+    fs::rename(&tmp_path, path).await?;
+    Ok(())
+}
+
+async fn remove_index_cache(url: &Url) {
+    if let Ok((body_path, meta_path)) = index_cache_paths(url) {
+        for path in &[meta_path, body_path] {
+            fs::remove_file(path).await.map_err(|e| {
+                if e.kind() != io::ErrorKind::NotFound {
+                    log::warn!("Cannot remove {:?}: {}", path, e);
+                }
+            }).ok();
+        }
+    }
+}
+
+/// Parses JSON reporting `(consumed, total)` bytes to the `progress`
+///
+/// Parsing stops with `Cancelled` error as soon as `cancel` is set.
+pub fn parse_json<T>(data: &[u8], progress: &mut dyn FnMut(u64, u64),
+                     cancel: &AtomicBool)
+    -> anyhow::Result<T>
     where T: serde::de::DeserializeOwned,
 {
-    let body_bytes = get_header(url, true).await?
-        .body_bytes().await.map_err(HttpError)?;
+    let reader = ProgressReader {
+        data,
+        total: data.len() as u64,
+        progress,
+        cancel,
+    };
+    let reader = std::io::BufReader::with_capacity(PARSE_CHUNK_SIZE, reader);
+    let jd = &mut serde_json::Deserializer::from_reader(reader);
+    match serde_path_to_error::deserialize(jd) {
+        Ok(value) => Ok(value),
+        Err(_) if cancel.load(Ordering::SeqCst) => Err(Cancelled.into()),
+        Err(e) => Err(e.into()),
+    }
+}
 
-    let jd = &mut serde_json::Deserializer::from_slice(&body_bytes);
-    Ok(serde_path_to_error::deserialize(jd)?)
+struct ProgressReader<'a> {
+    data: &'a [u8],
+    total: u64,
+    progress: &'a mut dyn FnMut(u64, u64),
+    cancel: &'a AtomicBool,
+}
+
+impl std::io::Read for ProgressReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.cancel.load(Ordering::SeqCst) {
+            return Err(std::io::Error::new(std::io::ErrorKind::Other,
+                                           Cancelled));
+        }
+        let bytes = std::io::Read::read(&mut self.data, buf)?;
+        (self.progress)(self.total - self.data.len() as u64, self.total);
+        Ok(bytes)
+    }
+}
+
+/// Shows a spinner and lets user interrupt parsing of a large index
+///
+/// Ctrl-C sets the cancellation flag, so parsing stops at the next chunk.
+/// The process exits with the usual interrupt status when this is dropped.
+#[derive(Default)]
+struct ParseProgress {
+    active: Option<(Interrupt, ProgressBar)>,
+}
+
+impl ParseProgress {
+    fn update(&mut self, consumed: u64, total: u64, cancel: &AtomicBool) {
+        if total < PARSE_PROGRESS_MIN_SIZE {
+            return;
+        }
+        if consumed >= total {
+            if let Some((_, bar)) = self.active.take() {
+                bar.finish_and_clear();
+            }
+            return;
+        }
+        let (interrupt, bar) = self.active.get_or_insert_with(|| {
+            (Interrupt::ctrl_c(), parse_bar(total))
+        });
+        bar.set_position(consumed);
+        if interrupt.is_set() {
+            cancel.store(true, Ordering::SeqCst);
+        }
+    }
+}
+
+fn parse_bar(total: u64) -> ProgressBar {
+    if !show_progress() {
+        return ProgressBar::hidden();
+    }
+    let bar = ProgressBar::new(total);
+    bar.set_style(
+        ProgressStyle::default_spinner()
+        .template("{spinner} Parsing package index \
+                   {bytes:>7.dim}/{total_bytes:7}"));
+    bar
+}
+
+async fn _get_json<T>(url: &Url, progress: &mut dyn FnMut(u64, u64),
+                      cancel: &AtomicBool)
+    -> Result<T, anyhow::Error>
+    where T: serde::de::DeserializeOwned,
+{
+    let body_bytes = get_body(url, use_index_cache()).await?;
+    match parse_json(&body_bytes, progress, cancel) {
+        Ok(data) => return Ok(data),
+        Err(e) if e.is::<Cancelled>() => return Err(e),
+        Err(e) => {
+            // cached copy might be truncated or the server might have
+            // responded with "not modified" to the broken index
+            log::warn!("Cannot decode index {}: {:#}. Fetching it again.",
+                       url, e);
+            remove_index_cache(url).await;
+        }
+    }
+    let body_bytes = get_body(url, false).await?;
+    let result = parse_json(&body_bytes, progress, cancel);
+    if matches!(&result, Err(e) if !e.is::<Cancelled>()) {
+        remove_index_cache(url).await;
+    }
+    result
 }
 
-#[context("failed to fetch JSON at URL: {}", url)]
 async fn get_json<T>(url: &Url) -> Result<T, anyhow::Error>
     where T: serde::de::DeserializeOwned,
 {
-    _get_json(url).race(async {
+    get_json_with(url, &mut |_, _| {}, &AtomicBool::new(false)).await
+}
+
+#[context("failed to fetch JSON at URL: {}", url)]
+async fn get_json_with<T>(url: &Url, progress: &mut dyn FnMut(u64, u64),
+                          cancel: &AtomicBool)
+    -> Result<T, anyhow::Error>
+    where T: serde::de::DeserializeOwned,
+{
+    _get_json(url, progress, cancel).race(async {
         task::sleep(Duration::from_secs(2)).await;
         if atty::is(atty::Stream::Stderr) {
             eprintln!("Fetching {} takes too long. Common reasons are:",
@@ -240,29 +1338,74 @@ async fn get_json<T>(url: &Url) -> Result<T, anyhow::Error>
 }
 
 fn filter_package(pkg_root: &Url, pkg: &PackageData) -> Option<PackageInfo> {
-    let result = _filter_package(pkg_root, pkg);
-    if result.is_none() {
-        log::info!("Skipping package {:?}", pkg);
+    match _filter_package(pkg_root, pkg) {
+        Ok(info) => Some(info),
+        Err(reason) => {
+            log::info!("Skipping package {} {}: {}",
+                       pkg.basename, pkg.version, reason);
+            None
+        }
     }
-    return result;
 }
 
-fn _filter_package(pkg_root: &Url, pkg: &PackageData) -> Option<PackageInfo> {
-    let iref = pkg.installrefs.iter()
-        .filter(|r| (
-                r.kind == "application/x-tar" &&
-                r.encoding.as_ref().map(|x| &x[..]) == Some("zstd") &&
-                r.verification.blake2b.as_ref()
-                    .map(valid_hash).unwrap_or(false)
-        ))
-        .next()?;
-    Some(PackageInfo {
-        version: pkg.version.parse().ok()?,
-        url: pkg_root.join(&iref.path).ok()?,
-        hash: PackageHash::Blake2b(
-            iref.verification.blake2b.as_ref()?[..].into()),
-        kind: PackageType::TarZst,
+/// Returns package type of the install ref or the reason it's unusable
+fn install_ref_type(iref: &InstallRef) -> Result<PackageType, String> {
+    let kind = match (&iref.kind[..], iref.encoding.as_deref()) {
+        ("application/x-tar", Some("zstd")) => PackageType::TarZst,
+        ("application/x-tar", Some(enc)) => {
+            return Err(format!("unsupported encoding {:?}", enc));
+        }
+        ("application/x-tar", None) => return Err("missing encoding".into()),
+        ("application/zip", _) => PackageType::Zip,
+        (kind, _) => return Err(format!("unsupported type {:?}", kind)),
+    };
+    if iref.verification.hash().is_none() {
+        return Err("no valid hash".into());
+    }
+    Ok(kind)
+}
+
+fn _filter_package(pkg_root: &Url, pkg: &PackageData)
+    -> Result<PackageInfo, String>
+{
+    let version = pkg.version.parse()
+        .map_err(|e| format!("unparsable version: {}", e))?;
+    if pkg.installrefs.is_empty() {
+        return Err("no install refs".into());
+    }
+    let mut tar = None;
+    let mut zip = None;
+    let mut rejected = Vec::new();
+    for iref in &pkg.installrefs {
+        if iref.kind == "application/pgp-signature" {
+            continue;
+        }
+        match install_ref_type(iref) {
+            Ok(PackageType::TarZst) => { tar.get_or_insert(iref); }
+            Ok(PackageType::Zip) => { zip.get_or_insert(iref); }
+            Err(reason) => rejected.push(format!("{}: {}", iref.path, reason)),
+        }
+    }
+    // tar.zst is preferred when both are available
+    let (iref, kind) = match (tar, zip) {
+        (Some(iref), _) => (iref, PackageType::TarZst),
+        (None, Some(iref)) => (iref, PackageType::Zip),
+        (None, None) => {
+            return Err(format!("no usable install ref ({})",
+                               rejected.join("; ")));
+        }
+    };
+    let signature = pkg.installrefs.iter()
+        .find(|r| r.kind == "application/pgp-signature")
+        .and_then(|r| pkg_root.join(&r.path).ok());
+    Ok(PackageInfo {
+        version,
+        url: pkg_root.join(&iref.path)
+            .map_err(|e| format!("invalid path {:?}: {}", iref.path, e))?,
+        hash: iref.verification.hash().ok_or("no valid hash")?,
+        kind,
         size: iref.verification.size,
+        signature,
     })
 }
 
@@ -283,7 +1426,7 @@ fn _filter_cli_package(pkg_root: &Url, pkg: &PackageData)
         .filter(|r| (
                 r.encoding.as_ref().map(|x| &x[..]) == Some("zstd") &&
                 r.verification.blake2b.as_ref()
-                    .map(valid_hash).unwrap_or(false)
+                    .map(|h| valid_hash(h, BLAKE2B_HEX_LEN)).unwrap_or(false)
         ))
         .next()
         .or_else(|| {
@@ -291,7 +1434,8 @@ fn _filter_cli_package(pkg_root: &Url, pkg: &PackageData)
             .filter(|r| (
                     r.encoding.as_ref().map(|x| &x[..]) == Some("identity") &&
                     r.verification.blake2b.as_ref()
-                        .map(valid_hash).unwrap_or(false)
+                        .map(|h| valid_hash(h, BLAKE2B_HEX_LEN))
+                        .unwrap_or(false)
             ))
             .next()
         })?;
@@ -310,9 +1454,34 @@ fn _filter_cli_package(pkg_root: &Url, pkg: &PackageData)
     })
 }
 
-fn valid_hash(val: &String) -> bool {
-    val.len() == 128 &&
-        hex::decode(val).map(|x| x.len() == 64).unwrap_or(false)
+fn valid_hash(val: &str, hex_len: usize) -> bool {
+    val.len() == hex_len &&
+        hex::decode(val).map(|x| x.len() == hex_len/2).unwrap_or(false)
+}
+
+impl Verification {
+    /// Returns the first valid hash in `hash_preference()` order
+    fn hash(&self) -> Option<PackageHash> {
+        self.hash_with(hash_preference())
+    }
+    fn hash_with(&self, preference: &[HashKind]) -> Option<PackageHash> {
+        preference.iter().find_map(|kind| {
+            let (hash, hex_len) = match kind {
+                HashKind::Blake3 => (self.blake3.as_ref()?, BLAKE3_HEX_LEN),
+                HashKind::Blake2b => (self.blake2b.as_ref()?, BLAKE2B_HEX_LEN),
+                HashKind::Sha256 => (self.sha256.as_ref()?, SHA256_HEX_LEN),
+            };
+            if !valid_hash(hash, hex_len) {
+                return None;
+            }
+            let hash = hash[..].into();
+            Some(match kind {
+                HashKind::Blake3 => PackageHash::Blake3(hash),
+                HashKind::Blake2b => PackageHash::Blake2b(hash),
+                HashKind::Sha256 => PackageHash::Sha256(hash),
+            })
+        })
+    }
 }
 
 pub fn get_cli_packages(channel: Channel)
@@ -326,9 +1495,10 @@ pub fn get_platform_cli_packages(channel: Channel, platform: &str)
 {
     use Channel::*;
 
-    let pkg_root = pkg_root()?;
+    let pkg_root = platform_pkg_root(platform)?;
     let url = pkg_root.join(&match channel {
         Stable => format!("/archive/.jsonindexes/{}.json", platform),
+        Testing => format!("/archive/.jsonindexes/{}.testing.json", platform),
         Nightly => format!("/archive/.jsonindexes/{}.nightly.json", platform),
     })?;
     let data: RepositoryData = match task::block_on(get_json(&url)) {
@@ -350,71 +1520,440 @@ pub fn get_server_packages(channel: Channel)
     get_platform_server_packages(channel, plat)
 }
 
+/// Same as `get_server_packages` but for an explicit `platform`
+/// (e.g. `x86_64-unknown-linux-gnu`) instead of the current host
+pub fn get_server_packages_for(channel: Channel, platform: &str)
+    -> anyhow::Result<Vec<PackageInfo>>
+{
+    get_platform_server_packages(channel, platform)
+}
+
+fn server_packages_memo() -> &'static Mutex<HashMap<(Channel, String), MemoEntry>>
+{
+    SERVER_PACKAGES.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Fetches the index at most once per channel and platform, so that
+/// installing several packages doesn't parse the same index again
+///
+/// Entries expire after the same time as the index cache on disk, so
+/// long-running processes still see new packages. Both caches are bypassed
+/// if `EDGEDB_NO_INDEX_CACHE` is set.
+///
+/// Parsing of large indexes shows a spinner and can be cancelled by Ctrl-C.
 fn get_platform_server_packages(channel: Channel, platform: &str)
     -> anyhow::Result<Vec<PackageInfo>>
+{
+    let cancel = AtomicBool::new(false);
+    let mut progress = ParseProgress::default();
+    get_platform_server_packages_with(channel, platform,
+        &mut |consumed, total| progress.update(consumed, total, &cancel),
+        &cancel)
+}
+
+fn get_platform_server_packages_with(channel: Channel, platform: &str,
+                                     progress: &mut dyn FnMut(u64, u64),
+                                     cancel: &AtomicBool)
+    -> anyhow::Result<Vec<PackageInfo>>
+{
+    let key = (channel, platform.to_string());
+    if let Some(entry) = server_packages_memo().lock().unwrap().get(&key) {
+        if use_index_cache() && entry.fetched.elapsed() < INDEX_CACHE_MAX_AGE {
+            log::debug!("Using already fetched index for {:?}", key);
+            return Ok(entry.packages.clone());
+        }
+    }
+    let packages = fetch_platform_server_packages(channel, platform,
+                                                  progress, cancel)?;
+    server_packages_memo().lock().unwrap().insert(key, MemoEntry {
+        fetched: Instant::now(),
+        packages: packages.clone(),
+    });
+    Ok(packages)
+}
+
+fn fetch_platform_server_packages(channel: Channel, platform: &str,
+                                  progress: &mut dyn FnMut(u64, u64),
+                                  cancel: &AtomicBool)
+    -> anyhow::Result<Vec<PackageInfo>>
 {
     use Channel::*;
 
-    let pkg_root = pkg_root()?;
-    let url = pkg_root.join(&match channel {
+    let roots = platform_pkg_roots(platform)?;
+    let index = match channel {
         Stable => format!("/archive/.jsonindexes/{}.json", platform),
+        Testing => format!("/archive/.jsonindexes/{}.testing.json", platform),
         Nightly => format!("/archive/.jsonindexes/{}.nightly.json", platform),
-    })?;
-    let data: RepositoryData = match task::block_on(get_json(&url)) {
-        Ok(data) => data,
-        Err(e) if e.is::<NotFound>() => RepositoryData { packages: vec![] },
-        Err(e) => return Err(e),
     };
-    let packages = data.packages.iter()
+    for (idx, pkg_root) in roots.iter().enumerate() {
+        let url = pkg_root.join(&index)?;
+        let is_last = idx + 1 == roots.len();
+        match task::block_on(get_cached_json(&url, progress, cancel)) {
+            Ok(data) => {
+                log::info!("Using package root {}", pkg_root);
+                return filter_server_packages(pkg_root, url, data);
+            }
+            Err(e) if !is_last &&
+                (e.is::<NotFound>() || e.is::<HttpFailure>())
+            => {
+                log::warn!("{:#}. Trying next package root.", e);
+            }
+            Err(e) if e.is::<NotFound>() => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(Vec::new())
+}
+
+fn filter_server_packages(pkg_root: &Url, url: Url, data: RepositoryData)
+    -> anyhow::Result<Vec<PackageInfo>>
+{
+    let server_packages = data.packages.iter()
         .filter(|pkg| pkg.basename == "edgedb-server")
-        .filter_map(|p| filter_package(&pkg_root, p))
+        .collect::<Vec<_>>();
+    if let Some(min) = min_package_count() {
+        if server_packages.len() < min {
+            return Err(IncompleteIndex {
+                url,
+                count: server_packages.len(),
+                min,
+            }.into());
+        }
+    }
+    let packages = server_packages.into_iter()
+        .filter_map(|p| filter_package(pkg_root, p))
         .collect();
     Ok(packages)
 }
 
 pub fn get_server_package(query: &Query)
     -> anyhow::Result<Option<PackageInfo>>
+{
+    Ok(get_server_packages_matching(query)?.into_iter().next())
+}
+
+/// Returns all packages matching the `query`, newest first
+pub fn get_server_packages_matching(query: &Query)
+    -> anyhow::Result<Vec<PackageInfo>>
 {
     let plat = platform::get_server()?;
-    get_platform_server_package(query, plat)
+    get_platform_server_packages_matching(query, plat)
+}
+
+fn get_platform_server_packages_matching(query: &Query, platform: &str)
+    -> anyhow::Result<Vec<PackageInfo>>
+{
+    let filter = query.version.as_ref();
+    let range = query.range.as_ref();
+    let mut pkgs = get_server_packages_for(query.channel, platform)?
+        .into_iter()
+        .filter(|pkg| filter.map(|q| q.matches(&pkg.version)).unwrap_or(true))
+        .filter(|pkg| range.map(|r| r.matches(&pkg.version)).unwrap_or(true))
+        .filter(|pkg| query.matches_hash(&pkg.version))
+        .collect::<Vec<_>>();
+    if pkgs.is_empty() && query.build_hash.is_some() {
+        return Err(BuildNotFound(query.display().to_string()).into());
+    }
+    pkgs.sort_by(|a, b| b.version.specific().cmp(&a.version.specific()));
+    Ok(pkgs)
+}
+
+pub fn get_specific_package(version: &ver::Specific)
+    -> anyhow::Result<Option<PackageInfo>>
+{
+    let channel = Channel::from_version(version)?;
+    let pkg = get_server_packages(channel)?.into_iter()
+        .filter(|pkg| &pkg.version.specific() == version)
+        .next();
+    Ok(pkg)
+}
+
+/// Returns the newest package in the channel that is at least `min_version`
+pub fn get_server_package_at_least(channel: Channel,
+                                   min_version: &ver::Specific)
+    -> anyhow::Result<Option<PackageInfo>>
+{
+    let pkg = get_server_packages(channel)?.into_iter()
+        .filter(|pkg| &pkg.version.specific() >= min_version)
+        .max_by_key(|pkg| pkg.version.specific());
+    Ok(pkg)
+}
+
+pub async fn download(dest: impl AsRef<Path>, url: &Url, quiet: bool,
+                      permanent_warning: bool)
+    -> Result<(), anyhow::Error>
+{
+    download_with_stats(dest, url, None, quiet, permanent_warning).await?;
+    Ok(())
+}
+
+/// Same as `download` but also returns size and duration of the transfer
+///
+/// If `expected_size` is known, fails with `SizeMismatch` when either
+/// the server reports or the transfer yields a different size.
+pub async fn download_with_stats(dest: impl AsRef<Path>, url: &Url,
+                                 expected_size: Option<u64>,
+                                 quiet: bool, permanent_warning: bool)
+    -> Result<DownloadStats, anyhow::Error>
+{
+    download_with_bar(dest.as_ref(), url, expected_size, None,
+                      permanent_warning,
+                      |total| download_bar(total, quiet)).await
+}
+
+/// Same as `download` but the progress bar is created by `make_bar` when
+/// size of the file is known
+///
+/// Data is written to a temporary file next to `dest` which is renamed into
+/// place only after the transfer is complete and matches `expected_hash`
+/// (if any), so `dest` never contains a partial or corrupt download. The
+/// hash is computed while downloading, so the file is never read back.
+#[context("failed to download file at URL: {}", url)]
+async fn download_with_bar(dest: &Path, url: &Url,
+                           expected_size: Option<u64>,
+                           expected_hash: Option<&PackageHash>,
+                           permanent_warning: bool,
+                           make_bar: impl FnOnce(Option<u64>) -> ProgressBar)
+    -> Result<DownloadStats, anyhow::Error>
+{
+    let tmp_path = PathBuf::from(tmp_file_path(dest.as_ref()));
+    let kind = expected_hash.map(|h| h.kind()).unwrap_or(HashKind::Blake2b);
+    let result = async {
+        let (digest, stats) = download_to(&tmp_path, url, expected_size,
+                                          kind, permanent_warning,
+                                          make_bar).await?;
+        if let Some(expected) = expected_hash {
+            verify_download(&tmp_path, &digest, expected).await?;
+        }
+        fs::rename(&tmp_path, dest).await
+            .with_context(|| format!("cannot rename {:?} -> {:?}",
+                                     tmp_path, dest))?;
+        Ok(stats)
+    }.await;
+    if result.is_err() {
+        match fs::remove_file(&tmp_path).await {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => log::warn!("Cannot remove {:?}: {}", tmp_path, e),
+        }
+    }
+    result
+}
+
+/// Downloads `url` to `dest`, returns `kind` digest of the data
+async fn download_to(dest: &Path, url: &Url,
+                     expected_size: Option<u64>,
+                     kind: HashKind,
+                     permanent_warning: bool,
+                     make_bar: impl FnOnce(Option<u64>) -> ProgressBar)
+    -> Result<(Vec<u8>, DownloadStats), anyhow::Error>
+{
+    let start = Instant::now();
+    log::info!("Downloading {} -> {}", url, dest.display());
+    let Fetched { mut response, final_url } =
+        get_header(url, permanent_warning).await?;
+    if &final_url != url {
+        log::info!("Resolved {} to {}", url, final_url);
+    }
+    let mut body = response.take_body();
+    let content_length = body.len().map(|len| len as u64);
+    if let (Some(expected), Some(actual)) = (expected_size, content_length) {
+        if expected != actual {
+            return Err(SizeMismatch {
+                what: "Content-Length",
+                expected,
+                actual,
+            }.into());
+        }
+    }
+    let mut out = fs::File::create(dest).await
+        .with_context(|| format!("writing {:?}", dest.display()))?;
+
+    let total = content_length.or(expected_size);
+    let bar = make_bar(total);
+    let mut eta = EtaEstimator::new();
+    let mut hasher = Hasher::new(kind);
+    let mut buf = vec![0u8; buffer_size()];
+    let mut written = 0;
+    let mut resumes = 0;
+    loop {
+        let bytes = match read_body(&mut body, &mut buf).await {
+            Ok(bytes) => bytes,
+            // stalled stream is resumed from where it stopped, this is
+            // only possible when the size is known
+            Err(e) if is_stalled(&e) && total.is_some() &&
+                      resumes < retry_config().max_attempts
+            => {
+                if written >= total.unwrap() {
+                    break;
+                }
+                resumes += 1;
+                log::warn!("Download of {} stalled at {} bytes, resuming.",
+                           url, written);
+                // resume from the canonical location to skip redirects
+                match get_range(&final_url, written, total.unwrap()).await {
+                    Ok(mut res) => body = res.take_body(),
+                    // appending a full body would corrupt the file
+                    Err(e) if e.is::<RangeIgnored>() => {
+                        log::warn!("{:#}. Restarting download of {} \
+                                    from the beginning.", e, url);
+                        out.set_len(0).await?;
+                        out.seek(SeekFrom::Start(0)).await?;
+                        hasher = Hasher::new(kind);
+                        written = 0;
+                        bar.set_position(0);
+                        eta = EtaEstimator::new();
+                        body = get_header(&final_url, false).await?
+                            .response.take_body();
+                    }
+                    Err(e) => return Err(e),
+                }
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        if bytes == 0 {
+            break;
+        }
+        throttle(bytes).await;
+        out.write_all(&buf[..bytes]).await?;
+        hasher.update(&buf[..bytes]);
+        written += bytes as u64;
+        bar.inc(bytes as u64);
+        eta.tick(&bar);
+    }
+    bar.finish();
+    drop(out);
+    if let Some(expected) = expected_size {
+        if written != expected {
+            return Err(SizeMismatch {
+                what: "downloaded file",
+                expected,
+                actual: written,
+            }.into());
+        }
+    }
+
+    let stats = DownloadStats { bytes: written, elapsed: start.elapsed() };
+    Ok((hasher.finalize(), stats))
+}
+
+/// Same as `download` but fails with `ChecksumMismatch` if the file doesn't
+/// match the `expected` hash
+pub async fn download_verified(dest: impl AsRef<Path>, url: &Url,
+                               expected: &PackageHash,
+                               expected_size: Option<u64>,
+                               quiet: bool, permanent_warning: bool)
+    -> Result<(), anyhow::Error>
+{
+    let dest = dest.as_ref();
+    if copy_from_pkg_cache(dest, expected).await? {
+        return Ok(());
+    }
+    let stats = download_with_bar(dest, url, expected_size,
+        Some(expected), permanent_warning,
+        |total| download_bar(total, quiet)).await?;
+    stats.log(url);
+    Ok(())
 }
 
-fn get_platform_server_package(query: &Query, platform: &str)
-    -> anyhow::Result<Option<PackageInfo>>
+/// Downloads files concurrently, verifying each one against its hash
+///
+/// At most `download_jobs()` transfers are active at once and their progress
+/// bars are stacked. All downloads are attempted even if some of them fail,
+/// errors are reported together.
+pub fn download_all(targets: &[(PathBuf, Url, PackageHash)])
+    -> anyhow::Result<()>
 {
-    let filter = query.version.as_ref();
-    let pkg = get_platform_server_packages(query.channel, platform)?
-        .into_iter()
-        .filter(|pkg| filter.map(|q| q.matches(&pkg.version)).unwrap_or(true))
-        .max_by_key(|pkg| pkg.version.specific());
-    Ok(pkg)
+    let multi = MultiProgress::new();
+    // all bars are added upfront, so that `join` doesn't exit early
+    let bars = targets.iter()
+        .map(|_| multi.add(download_bar(Some(0), false)))
+        .collect::<Vec<_>>();
+    let results = crossbeam_utils::thread::scope(|scope| {
+        let drawer = scope.spawn(|_| multi.join());
+        let mut results = task::block_on(
+            stream::iter(targets.iter().zip(&bars).enumerate())
+            .map(|(idx, ((dest, url, expected), bar))| async move {
+                let result = async {
+                    if copy_from_pkg_cache(dest, expected).await? {
+                        return Ok(());
+                    }
+                    let stats = download_with_bar(dest, url, None,
+                                                  Some(expected), true,
+                        |total| {
+                            bar.set_length(total.unwrap_or(0));
+                            bar.clone()
+                        }).await?;
+                    stats.log(url);
+                    Ok::<_, anyhow::Error>(())
+                }.await;
+                bar.finish_and_clear();
+                (idx, result)
+            })
+            .buffer_unordered(download_jobs())
+            .collect::<Vec<_>>()
+        );
+        drawer.join().ok();
+        results.sort_by_key(|(idx, _)| *idx);
+        results
+    }).map_err(|_| anyhow::anyhow!("download thread panicked"))?;
+
+    let errors = targets.iter().zip(results)
+        .filter_map(|((_, url, _), (_, result))| {
+            result.err().map(|e| format!("{}: {:#}", url, e))
+        })
+        .collect::<Vec<_>>();
+    if !errors.is_empty() {
+        anyhow::bail!("{} of {} downloads failed:\n  {}",
+                      errors.len(), targets.len(), errors.join("\n  "));
+    }
+    Ok(())
 }
 
-pub fn get_specific_package(version: &ver::Specific)
-    -> anyhow::Result<Option<PackageInfo>>
-{
-    let channel = Channel::from_version(version)?;
-    let pkg = get_server_packages(channel)?.into_iter()
-        .filter(|pkg| &pkg.version.specific() == version)
-        .next();
-    Ok(pkg)
+/// Maximum number of concurrent transfers in `download_all`
+///
+/// Overridden by `EDGEDB_DOWNLOAD_JOBS`.
+fn download_jobs() -> usize {
+    *DOWNLOAD_JOBS.get_or_init(|| {
+        env_clamped("EDGEDB_DOWNLOAD_JOBS",
+                    DEFAULT_DOWNLOAD_JOBS, 1, MAX_DOWNLOAD_JOBS)
+    })
 }
 
-#[context("failed to download file at URL: {}", url)]
-pub async fn download(dest: impl AsRef<Path>, url: &Url, quiet: bool,
-                      permanent_warning: bool)
-    -> Result<blake2b_simd::Hash, anyhow::Error>
+/// Checks `digest` of the downloaded file against the `expected` hash
+///
+/// The `digest` must be computed by the `expected.kind()` algorithm. The
+/// file is removed on mismatch. Hashes of unknown format can't be
+/// verified, so only a warning is logged for them.
+pub async fn verify_download(dest: impl AsRef<Path>, digest: &[u8],
+                             expected: &PackageHash)
+    -> anyhow::Result<()>
 {
     let dest = dest.as_ref();
-    log::info!("Downloading {} -> {}", url, dest.display());
-    let mut body = get_header(url, permanent_warning).await?.take_body();
-    let mut out = fs::File::create(dest).await
-        .with_context(|| format!("writing {:?}", dest.display()))?;
+    if !expected.verify_digest(digest) {
+        let expected = match expected {
+            PackageHash::Blake3(hex) | PackageHash::Blake2b(hex) |
+            PackageHash::Sha256(hex) => hex,
+            PackageHash::Unknown(val) => val,
+        };
+        fs::remove_file(dest).await
+            .map_err(|e| {
+                log::warn!("Failed to remove {:?}: {}", dest, e);
+            }).ok();
+        return Err(ChecksumMismatch {
+            expected: expected.to_string(),
+            actual: hex::encode(digest),
+        }.into());
+    }
+    Ok(())
+}
 
-    let bar = if quiet {
+fn download_bar(len: Option<u64>, quiet: bool) -> ProgressBar {
+    let bar = if quiet || !show_progress() {
         ProgressBar::hidden()
-    } else if let Some(len) = body.len() {
-        ProgressBar::new(len as u64)
+    } else if let Some(len) = len {
+        ProgressBar::new(len)
     } else {
         ProgressBar::new_spinner()
     };
@@ -423,22 +1962,223 @@ pub async fn download(dest: impl AsRef<Path>, url: &Url, quiet: bool,
         .template(
             "{elapsed_precise} [{bar}] \
             {bytes:>7.dim}/{total_bytes:7} \
-            {binary_bytes_per_sec:.dim} | ETA: {eta}")
+            {binary_bytes_per_sec:.dim} | ETA: {msg}")
         .progress_chars("=> "));
-    let mut hasher = blake2b_simd::State::new();
-    let mut buf = [0u8; 16384];
+    bar.set_message("-");
+    bar
+}
+
+fn segment_ranges(size: u64, segments: u64) -> Vec<(u64, u64)> {
+    let segments = segments.max(1);
+    let seg_size = (size + segments - 1) / segments;
+    (0..segments)
+        .map(|i| (i*seg_size, min(size, (i+1)*seg_size)))
+        .filter(|(start, end)| start < end)
+        .collect()
+}
+
+/// Downloads file using `segments` concurrent range requests
+///
+/// Each segment is written at its offset in `dest`. Segments are fed into
+/// the hasher in offset order as soon as all preceding segments are
+/// complete, so hashing overlaps with downloading the rest of the file.
+///
+/// Progress of each segment is persisted in a sidecar file next to `dest`,
+/// so that if download is interrupted, next run only fetches the parts that
+/// are still missing.
+#[context("failed to download file at URL: {}", url)]
+pub async fn download_segmented(dest: impl AsRef<Path>, url: &Url,
+                                size: u64, segments: u64, kind: HashKind,
+                                quiet: bool)
+    -> Result<Vec<u8>, anyhow::Error>
+{
+    let dest = dest.as_ref();
+    let ranges = segment_ranges(size, segments);
+    let state_path = segment_state_path(dest);
+    let mut state = match read_segment_state(dest, &state_path, size,
+                                             &ranges).await {
+        Some(state) => {
+            log::info!("Resuming download {} -> {} in {} segments",
+                       url, dest.display(), segments);
+            state
+        }
+        None => {
+            log::info!("Downloading {} -> {} in {} segments",
+                       url, dest.display(), segments);
+            let out = fs::File::create(dest).await
+                .with_context(|| format!("writing {:?}", dest.display()))?;
+            out.set_len(size).await?;
+            drop(out);
+            SegmentState {
+                size,
+                ranges: ranges.clone(),
+                completed: vec![0; ranges.len()],
+            }
+        }
+    };
+
+    let bar = download_bar(Some(size), quiet);
+    bar.inc(state.completed.iter().sum());
+    let (tx, rx) = channel::unbounded();
+    for (idx, &(start, end)) in ranges.iter().enumerate() {
+        let offset = start + state.completed[idx];
+        if offset >= end {
+            tx.send(SegmentEvent::Done(idx)).await.ok();
+            continue;
+        }
+        let dest = dest.to_path_buf();
+        let url = url.clone();
+        let bar = bar.clone();
+        let tx = tx.clone();
+        task::spawn(async move {
+            let res = download_segment(&dest, &url, idx, start, offset, end,
+                                       &bar, &tx).await;
+            tx.send(match res {
+                Ok(()) => SegmentEvent::Done(idx),
+                Err(e) => SegmentEvent::Failed(e),
+            }).await.ok();
+        });
+    }
+    drop(tx);
+
+    let mut done = vec![false; ranges.len()];
+    let mut next = 0;
+    let mut hasher = Hasher::new(kind);
+    let mut last_save = Instant::now();
+    let mut eta = EtaEstimator::new();
+    while let Ok(event) = rx.recv().await {
+        match event {
+            SegmentEvent::Progress(idx, completed) => {
+                state.completed[idx] = completed;
+                eta.tick(&bar);
+                if last_save.elapsed() > SEGMENT_STATE_SAVE_INTERVAL {
+                    write_segment_state(&state_path, &state).await?;
+                    last_save = Instant::now();
+                }
+            }
+            SegmentEvent::Done(idx) => {
+                let (start, end) = ranges[idx];
+                state.completed[idx] = end - start;
+                done[idx] = true;
+                while next < ranges.len() && done[next] {
+                    let (start, end) = ranges[next];
+                    hash_range(dest, start, end, &mut hasher).await?;
+                    next += 1;
+                }
+            }
+            SegmentEvent::Failed(e) => {
+                write_segment_state(&state_path, &state).await
+                    .map_err(|e| log::warn!("Cannot save download state: {:#}",
+                                            e))
+                    .ok();
+                return Err(e);
+            }
+        }
+    }
+    if next < ranges.len() {
+        anyhow::bail!("segment {} was not downloaded", next);
+    }
+    bar.finish();
+    match fs::remove_file(&state_path).await {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => log::warn!("Cannot remove {:?}: {}", state_path, e),
+    }
+
+    Ok(hasher.finalize())
+}
+
+fn segment_state_path(dest: &Path) -> PathBuf {
+    let mut name = dest.file_name().unwrap_or_default().to_os_string();
+    name.push(".segments");
+    dest.with_file_name(name)
+}
+
+/// Returns saved progress if it matches the download being started
+async fn read_segment_state(dest: &Path, state_path: &Path, size: u64,
+                            ranges: &[(u64, u64)])
+    -> Option<SegmentState>
+{
+    let data = fs::read(state_path).await.ok()?;
+    let state: SegmentState = match serde_json::from_slice(&data) {
+        Ok(state) => state,
+        Err(e) => {
+            log::warn!("Ignoring invalid download state {:?}: {}",
+                       state_path, e);
+            return None;
+        }
+    };
+    let file_len = fs::metadata(dest).await.ok()?.len();
+    if state.size != size || file_len != size || state.ranges != ranges
+        || state.completed.len() != ranges.len()
+        || state.completed.iter().zip(ranges)
+            .any(|(done, (start, end))| *done > end - start)
+    {
+        log::info!("Download state {:?} does not match package, \
+                    starting from scratch", state_path);
+        return None;
+    }
+    Some(state)
+}
+
+async fn write_segment_state(path: &Path, state: &SegmentState)
+    -> anyhow::Result<()>
+{
+    fs::write(path, serde_json::to_vec(state)?).await
+        .with_context(|| format!("writing {:?}", path.display()))?;
+    Ok(())
+}
+
+async fn download_segment(dest: &PathBuf, url: &Url, idx: usize,
+                          start: u64, offset: u64, end: u64,
+                          bar: &ProgressBar,
+                          progress: &channel::Sender<SegmentEvent>)
+    -> anyhow::Result<()>
+{
+    let mut body = get_range(url, offset, end).await?.take_body();
+    let mut out = fs::OpenOptions::new().write(true).open(dest).await?;
+    out.seek(SeekFrom::Start(offset)).await?;
+    let mut buf = vec![0u8; buffer_size()];
+    let mut written = offset - start;
     loop {
-        let bytes = body.read(&mut buf).await?;
+        let bytes = read_body(&mut body, &mut buf).await?;
         if bytes == 0 {
             break;
         }
+        if start + written + bytes as u64 > end {
+            anyhow::bail!("server sent more data than requested \
+                           for range {}-{}", offset, end);
+        }
+        throttle(bytes).await;
         out.write_all(&buf[..bytes]).await?;
-        hasher.update(&buf[..bytes]);
+        // only report bytes that have reached the file
+        out.flush().await?;
+        written += bytes as u64;
         bar.inc(bytes as u64);
+        progress.send(SegmentEvent::Progress(idx, written)).await.ok();
     }
-    bar.finish();
+    if start + written != end {
+        anyhow::bail!("segment {}-{} is truncated: received {} bytes",
+                      start, end, written);
+    }
+    Ok(())
+}
 
-    Ok(hasher.finalize())
+async fn hash_range(path: &Path, start: u64, end: u64,
+                    hasher: &mut Hasher)
+    -> anyhow::Result<()>
+{
+    let mut file = fs::File::open(path).await?;
+    file.seek(SeekFrom::Start(start)).await?;
+    let mut buf = vec![0u8; buffer_size()];
+    let mut left = end - start;
+    while left > 0 {
+        let chunk = min(left, buf.len() as u64) as usize;
+        file.read_exact(&mut buf[..chunk]).await?;
+        hasher.update(&buf[..chunk]);
+        left -= chunk as u64;
+    }
+    Ok(())
 }
 
 impl fmt::Display for PackageInfo {
@@ -447,14 +2187,86 @@ impl fmt::Display for PackageInfo {
     }
 }
 
+impl DownloadStats {
+    pub fn bytes_per_second(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0.0 {
+            self.bytes as f64 / secs
+        } else {
+            0.0
+        }
+    }
+    /// Logs stats as a single `key=value` line for performance tracking
+    pub fn log(&self, url: &Url) {
+        log::info!(target: "edgedb::download_stats",
+                   "url={} bytes={} elapsed_ms={} bytes_per_sec={:.0}",
+                   url, self.bytes, self.elapsed.as_millis(),
+                   self.bytes_per_second());
+    }
+}
+
+/// Compares slices in time that doesn't depend on where they differ
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() &&
+        a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
 impl PackageHash {
+    /// Algorithm the digest for `verify_digest` must be computed with
+    ///
+    /// Hashes of unknown format can't be verified, any algorithm works
+    /// for them.
+    pub fn kind(&self) -> HashKind {
+        match self {
+            PackageHash::Blake3(_) => HashKind::Blake3,
+            PackageHash::Blake2b(_) | PackageHash::Unknown(_) => {
+                HashKind::Blake2b
+            }
+            PackageHash::Sha256(_) => HashKind::Sha256,
+        }
+    }
+    /// Checks raw digest of the data, computed by the `kind()` algorithm,
+    /// against the expected hash
+    ///
+    /// Hashes of unknown format can't be verified, so they are accepted
+    /// with a warning.
+    pub fn verify_digest(&self, digest: &[u8]) -> bool {
+        match self {
+            PackageHash::Blake3(hex) | PackageHash::Blake2b(hex) |
+            PackageHash::Sha256(hex) => {
+                match hex::decode(&hex[..]) {
+                    Ok(expected) => constant_time_eq(&expected, digest),
+                    Err(_) => false,
+                }
+            }
+            PackageHash::Unknown(val) => {
+                log::warn!("Cannot verify hash, unknown hash format {:?}", val);
+                true
+            }
+        }
+    }
+    /// Lowercase prefix of the hash used in cache file names
+    fn file_hash(&self) -> String {
+        let val = match self {
+            PackageHash::Blake3(val) | PackageHash::Blake2b(val) |
+            PackageHash::Sha256(val) => &val[..],
+            PackageHash::Unknown(val) => {
+                val.split_once(':').map(|(_, hash)| hash).unwrap_or(val)
+            }
+        };
+        val.chars().take(CACHE_HASH_LEN).collect::<String>()
+            .to_ascii_lowercase()
+    }
     fn short(&self) -> &str {
         match self {
+            PackageHash::Blake3(val) => &val[..7],
             PackageHash::Blake2b(val) => &val[..7],
+            PackageHash::Sha256(val) => &val[..7],
             PackageHash::Unknown(val) => {
-                let start = val.find(":")
+                // 7 chars after the prefix, or last 7 chars if no prefix
+                let start = val.find(':').map(|idx| idx + 1)
                     .unwrap_or(val.len().saturating_sub(7));
-                &val[start..min(7, val.len() - start)]
+                val.get(start..min(start + 7, val.len())).unwrap_or(val)
             }
         }
     }
@@ -463,7 +2275,9 @@ impl PackageHash {
 impl fmt::Display for PackageHash {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
+            PackageHash::Blake3(val) => write!(f, "blake3:{}", val),
             PackageHash::Blake2b(val) => write!(f, "blake2b:{}", val),
+            PackageHash::Sha256(val) => write!(f, "sha256:{}", val),
             PackageHash::Unknown(val) => write!(f, "{}", val),
         }
     }
@@ -471,10 +2285,36 @@ impl fmt::Display for PackageHash {
 
 impl Query {
     pub fn nightly() -> Query {
-        Query { channel: Channel::Nightly, version: None }
+        Query {
+            channel: Channel::Nightly,
+            version: None,
+            range: None,
+            build_hash: None,
+        }
     }
     pub fn stable() -> Query {
-        Query { channel: Channel::Stable, version: None }
+        Query {
+            channel: Channel::Stable,
+            version: None,
+            range: None,
+            build_hash: None,
+        }
+    }
+    pub fn testing() -> Query {
+        Query {
+            channel: Channel::Testing,
+            version: None,
+            range: None,
+            build_hash: None,
+        }
+    }
+    /// Newest release in the stable channel
+    pub fn latest_stable() -> Query {
+        Query::stable()
+    }
+    /// Newest nightly build
+    pub fn latest_nightly() -> Query {
+        Query::nightly()
     }
     pub fn display(&self) -> QueryDisplay {
         QueryDisplay(self)
@@ -483,38 +2323,20 @@ impl Query {
         version: &Option<ver::Filter>)
         -> anyhow::Result<Query>
     {
-        let channel = if nightly {
-            Channel::Nightly
+        let query = if nightly {
+            Query::latest_nightly()
         } else {
-            Channel::Stable
+            Query::latest_stable()
         };
-        let version = version.clone();
-
-        Ok(Query { channel, version })
+        Ok(Query { version: version.clone(), ..query })
     }
     pub fn from_filter(ver: &ver::Filter) -> anyhow::Result<Query> {
-        use crate::portable::repository::ver::FilterMinor;
-        match ver.minor {
-            None => Ok(Query {
-                channel: Channel::Stable,
-                version: Some(ver.clone()),
-            }),
-            Some(FilterMinor::Alpha(_)) |
-            Some(FilterMinor::Beta(_)) |
-            Some(FilterMinor::Rc(_))
-            if ver.major == 1 || ver.major == 2 => Ok(Query {
-                channel: Channel::Stable,
-                version: Some(ver.clone()),
-            }),
-            Some(FilterMinor::Alpha(_)) |
-            Some(FilterMinor::Beta(_)) |
-            Some(FilterMinor::Rc(_))
-            => anyhow::bail!("prerelease channel is no supported yet"),
-            Some(FilterMinor::Minor(_)) => Ok(Query {
-                channel: Channel::Stable,
-                version: Some(ver.clone()),
-            }),
-        }
+        Ok(Query {
+            channel: Channel::from_filter(ver)?,
+            version: Some(ver.clone()),
+            range: None,
+            build_hash: None,
+        })
     }
     pub fn from_version(ver: &ver::Specific) -> anyhow::Result<Query> {
         use crate::portable::repository::ver::{MinorVersion, FilterMinor};
@@ -526,6 +2348,8 @@ impl Query {
                     major: ver.major,
                     minor: Some(FilterMinor::Alpha(v)),
                 }),
+                range: None,
+                build_hash: None,
             }),
             MinorVersion::Beta(v) if ver.major == 1 => Ok(Query {
                 channel: Channel::Stable,
@@ -533,13 +2357,17 @@ impl Query {
                     major: ver.major,
                     minor: Some(FilterMinor::Beta(v)),
                 }),
+                range: None,
+                build_hash: None,
             }),
-            MinorVersion::Rc(v) if ver.major == 1 || ver.major == 2 => Ok(Query {
+            MinorVersion::Rc(v) if ver.major == 1 => Ok(Query {
                 channel: Channel::Stable,
                 version: Some(ver::Filter {
                     major: ver.major,
                     minor: Some(FilterMinor::Rc(v)),
                 }),
+                range: None,
+                build_hash: None,
             }),
             MinorVersion::Minor(v) => Ok(Query {
                 channel: Channel::Stable,
@@ -547,15 +2375,57 @@ impl Query {
                     major: ver.major,
                     minor: Some(FilterMinor::Minor(v)),
                 }),
+                range: None,
+                build_hash: None,
+            }),
+            MinorVersion::Alpha(v) => Ok(Query {
+                channel: Channel::Testing,
+                version: Some(ver::Filter {
+                    major: ver.major,
+                    minor: Some(FilterMinor::Alpha(v)),
+                }),
+                range: None,
+                build_hash: None,
+            }),
+            MinorVersion::Beta(v) => Ok(Query {
+                channel: Channel::Testing,
+                version: Some(ver::Filter {
+                    major: ver.major,
+                    minor: Some(FilterMinor::Beta(v)),
+                }),
+                range: None,
+                build_hash: None,
+            }),
+            MinorVersion::Rc(v) => Ok(Query {
+                channel: Channel::Testing,
+                version: Some(ver::Filter {
+                    major: ver.major,
+                    minor: Some(FilterMinor::Rc(v)),
+                }),
+                range: None,
+                build_hash: None,
             }),
-            MinorVersion::Alpha(_) |
-            MinorVersion::Beta(_) |
-            MinorVersion::Rc(_) => {
-                anyhow::bail!("pre-release channel is not supported yet");
-            }
         }
     }
+    pub fn from_range(range: ver::Range) -> anyhow::Result<Query> {
+        let channel = match &range.lower {
+            Some(bound) => Channel::from_version(&bound.version)?,
+            None => Channel::Stable,
+        };
+        Ok(Query {
+            channel,
+            version: None,
+            range: Some(range),
+            build_hash: None,
+        })
+    }
     pub fn matches(&self, ver: &ver::Build) -> bool {
+        if !self.matches_hash(ver) {
+            return false;
+        }
+        if let Some(range) = &self.range {
+            return range.matches(ver);
+        }
         match &self.version {
             Some(query_ver) => query_ver.matches(ver),
             None => {
@@ -565,11 +2435,29 @@ impl Query {
             }
         }
     }
+    fn matches_hash(&self, ver: &ver::Build) -> bool {
+        match (&self.build_hash, ver.hash()) {
+            (None, _) => true,
+            (Some(prefix), Some(hash)) => hash.starts_with(&prefix[..]),
+            (Some(_), None) => false,
+        }
+    }
     pub fn as_config_value(&self) -> String {
+        let value = self.channel_config_value();
+        match &self.build_hash {
+            Some(hash) => format!("{}+{}", value, hash),
+            None => value,
+        }
+    }
+    fn channel_config_value(&self) -> String {
         if self.channel ==  Channel::Nightly {
             "nightly".into()
+        } else if let Some(range) = &self.range {
+            range.to_string()
         } else if let Some(ver) = &self.version {
             ver.to_string()
+        } else if self.channel == Channel::Testing {
+            "testing".into()
         } else {
             "*".into()
         }
@@ -588,6 +2476,15 @@ impl Serialize for PackageHash {
     }
 }
 
+impl Serialize for Query {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        serializer.serialize_str(&self.as_config_value())
+    }
+}
+
 impl Serialize for Channel {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -602,45 +2499,71 @@ impl<'de> Deserialize<'de> for PackageHash {
         where D: de::Deserializer<'de>,
     {
         let s: String = Deserialize::deserialize(deserializer)?;
+        if let Some(hash) = s.strip_prefix("blake3:") {
+            if hash.len() != BLAKE3_HEX_LEN {
+                return Err(de::Error::custom("invalid blake3 hash length"));
+            }
+            return Ok(PackageHash::Blake3(hash.into()));
+        }
         if let Some(hash) = s.strip_prefix("blake2b:") {
             if hash.len() != 128 {
                 return Err(de::Error::custom("invalid blake2b hash length"));
             }
             return Ok(PackageHash::Blake2b(hash.into()));
         }
+        if let Some(hash) = s.strip_prefix("sha256:") {
+            if hash.len() != SHA256_HEX_LEN {
+                return Err(de::Error::custom("invalid sha256 hash length"));
+            }
+            return Ok(PackageHash::Sha256(hash.into()));
+        }
         return Ok(PackageHash::Unknown(s.into()));
     }
 }
 
-impl std::str::FromStr for Query {
-    type Err = anyhow::Error;
-    fn from_str(s: &str) -> anyhow::Result<Query> {
-        if s == "*" {
-            return Ok(Query {
-                channel: Channel::Stable,
-                version: None,
-            });
-        } else if s == "nightly" {
-            return Ok(Query {
-                channel: Channel::Nightly,
-                version: None,
-            });
-        } else {
+/// Parses `*`, a channel name or a version filter such as `2.3`,
+/// optionally pinned to a build with a hash prefix: `2.3+abc1234`
+fn parse_query(s: &str) -> anyhow::Result<Query> {
+    if let Some((base, hash)) = s.split_once('+') {
+        if hash.is_empty() ||
+            !hash.chars().all(|c| c.is_ascii_alphanumeric())
+        {
+            anyhow::bail!("invalid build hash {:?}", hash);
+        }
+        let mut query = parse_query(base)?;
+        query.build_hash = Some(hash.to_ascii_lowercase());
+        return Ok(query);
+    }
+    match s {
+        "*" => Ok(Query::stable()),
+        "nightly" => Ok(Query::nightly()),
+        "testing" => Ok(Query::testing()),
+        _ if ver::Range::is_range(s) => Query::from_range(s.parse()?),
+        _ => {
             let ver: ver::Filter = s.parse()?;
-            return Ok(Query {
+            Ok(Query {
                 channel: Channel::from_filter(&ver)?,
                 version: Some(ver),
-            });
+                range: None,
+                build_hash: None,
+            })
         }
     }
 }
 
+impl std::str::FromStr for Query {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> anyhow::Result<Query> {
+        parse_query(s)
+    }
+}
+
 impl<'de> Deserialize<'de> for Query {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
         where D: de::Deserializer<'de>,
     {
         let s: String = Deserialize::deserialize(deserializer)?;
-        s.parse().map_err(serde::de::Error::custom)
+        parse_query(&s).map_err(serde::de::Error::custom)
     }
 }
 
@@ -649,33 +2572,28 @@ impl Channel {
         match ver.minor {
             ver::MinorVersion::Dev(_) => Ok(Channel::Nightly),
             ver::MinorVersion::Minor(_) => Ok(Channel::Stable),
-            _ if ver.major == 1 || ver.major == 2 => {
-                // before 1.0 all prereleases go into a stable channel
+            _ if ver.major == 1 => {
+                // before 2.0 all prereleases go into a stable channel
                 Ok(Channel::Stable)
             }
-            _ => {
-                anyhow::bail!("prerelease versions > 2.0 \
-                               are no supported yet");
-            }
+            _ => Ok(Channel::Testing),
         }
     }
     pub fn from_filter(ver: &ver::Filter) -> anyhow::Result<Channel> {
         match ver.minor {
             None => Ok(Channel::Stable),
             Some(ver::FilterMinor::Minor(_)) => Ok(Channel::Stable),
-            Some(_) if ver.major == 1 || ver.major == 2 => {
-                // before 1.0 all prereleases go into a stable channel
+            Some(_) if ver.major == 1 => {
+                // before 2.0 all prereleases go into a stable channel
                 Ok(Channel::Stable)
             }
-            Some(_) => {
-                anyhow::bail!("prerelease versions > 2.0 \
-                               are no supported yet");
-            }
+            Some(_) => Ok(Channel::Testing),
         }
     }
     pub fn as_str(&self) -> &str {
         match self {
             Channel::Nightly => "nightly",
+            Channel::Testing => "testing",
             Channel::Stable => "stable",
         }
     }
@@ -685,19 +2603,445 @@ impl fmt::Display for QueryDisplay<'_> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         use ver::FilterMinor::*;
 
-        match &self.0.version {
-            None => self.0.channel.as_str().fmt(f),
-            Some(ver) => {
-                ver.major.fmt(f)?;
-                f.write_str(".")?;
-                match ver.minor {
-                    None => "0".fmt(f),
-                    Some(Minor(m)) => m.fmt(f),
-                    Some(Alpha(v)) => write!(f, "0-alpha.{}", v),
-                    Some(Beta(v)) => write!(f, "0-beta.{}", v),
-                    Some(Rc(v)) => write!(f, "0-rc.{}", v),
+        if let Some(range) = &self.0.range {
+            range.fmt(f)?;
+        } else {
+            match &self.0.version {
+                None => self.0.channel.as_str().fmt(f)?,
+                Some(ver) => {
+                    ver.major.fmt(f)?;
+                    f.write_str(".")?;
+                    match ver.minor {
+                        None => "0".fmt(f)?,
+                        Some(Minor(m)) => m.fmt(f)?,
+                        Some(Alpha(v)) => write!(f, "0-alpha.{}", v)?,
+                        Some(Beta(v)) => write!(f, "0-beta.{}", v)?,
+                        Some(Rc(v)) => write!(f, "0-rc.{}", v)?,
+                    }
                 }
             }
         }
+        if let Some(hash) = &self.0.build_hash {
+            write!(f, "+{}", hash)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use test_case::test_case;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::{Duration, Instant, UNIX_EPOCH};
+
+    use super::{parse_size, parse_retry_after, parse_roots};
+    use super::{content_range_matches};
+    use super::{is_fresh, IndexCacheMeta, EtaEstimator};
+    use super::{parse_hash_preference, parse_json, Cancelled};
+    use super::{Channel, HashKind, PackageHash, Query, Verification};
+    use super::{_filter_package, InstallRef, PackageData, PackageType};
+
+    #[test_case("1024" => 1024)]
+    #[test_case("512K" => 512 << 10)]
+    #[test_case("2M" => 2 << 20)]
+    #[test_case("2MiB" => 2 << 20)]
+    #[test_case("1.5m" => 3 << 19)]
+    #[test_case("1GB" => 1 << 30)]
+    fn size(value: &str) -> u64 {
+        parse_size(value).unwrap()
+    }
+
+    #[test_case("")]
+    #[test_case("0")]
+    #[test_case("2X")]
+    #[test_case("M")]
+    fn bad_size(value: &str) {
+        assert!(parse_size(value).is_err());
+    }
+
+    #[test_case("bytes 100-199/200" => true)]
+    #[test_case("bytes 100-199/*" => true)]
+    #[test_case("bytes 0-199/200" => false)]
+    #[test_case("bytes 100-150/200" => false)]
+    #[test_case("bytes */200" => false)]
+    #[test_case("items 100-199/200" => false)]
+    fn content_range(value: &str) -> bool {
+        content_range_matches(value, 100, 200)
+    }
+
+    #[test_case("1.0-beta.2" => Channel::Stable)]
+    #[test_case("1.0-rc.3" => Channel::Stable)]
+    #[test_case("2.0-beta.1" => Channel::Testing)]
+    #[test_case("2.0-rc.1" => Channel::Testing)]
+    #[test_case("2.1" => Channel::Stable)]
+    #[test_case("3.0-alpha.1" => Channel::Testing)]
+    #[test_case("3.0-beta.2" => Channel::Testing)]
+    #[test_case("3.0-rc.1" => Channel::Testing)]
+    #[test_case("3.0-dev.7000" => Channel::Nightly)]
+    fn channel_from_version(ver: &str) -> Channel {
+        Query::from_version(&ver.parse().unwrap()).unwrap().channel
+    }
+
+    #[test_case("2" => Channel::Stable)]
+    #[test_case("1.0-alpha.2" => Channel::Stable)]
+    #[test_case("2.0-rc.1" => Channel::Testing)]
+    #[test_case("3.0-alpha.1" => Channel::Testing)]
+    #[test_case("3.0-rc.2" => Channel::Testing)]
+    #[test_case("testing" => Channel::Testing)]
+    #[test_case("nightly" => Channel::Nightly)]
+    fn channel_from_query(query: &str) -> Channel {
+        query.parse::<Query>().unwrap().channel
+    }
+
+    #[test_case("1.0-beta.2" => Channel::Stable)]
+    #[test_case("2.0-alpha.1" => Channel::Testing)]
+    #[test_case("2.0-rc.1" => Channel::Testing)]
+    #[test_case("2.0" => Channel::Stable)]
+    #[test_case("3.0-dev.7000" => Channel::Nightly)]
+    fn channel_of_version(ver: &str) -> Channel {
+        Channel::from_version(&ver.parse().unwrap()).unwrap()
+    }
+
+    #[test_case("1.0-rc.1" => Channel::Stable)]
+    #[test_case("2.0-beta.3" => Channel::Testing)]
+    #[test_case("2.0-rc.1" => Channel::Testing)]
+    #[test_case("2.1" => Channel::Stable)]
+    #[test_case("2" => Channel::Stable)]
+    fn channel_of_filter(ver: &str) -> Channel {
+        Channel::from_filter(&ver.parse().unwrap()).unwrap()
+    }
+
+    #[test_case("*")]
+    #[test_case("nightly")]
+    #[test_case("2.3")]
+    #[test_case("3.0-beta.1")]
+    fn query_deserialize_matches_parse(query: &str) {
+        let json = serde_json::to_string(query).unwrap();
+        let parsed: Query = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, query.parse().unwrap());
+    }
+
+    #[test_case("*")]
+    #[test_case("nightly")]
+    #[test_case("testing")]
+    #[test_case("2")]
+    #[test_case("3.0-beta.1")]
+    #[test_case(">=2.1,<3")]
+    #[test_case("2.3+abc1234")]
+    fn query_serialize_round_trip(query: &str) {
+        let query: Query = query.parse().unwrap();
+        let json = serde_json::to_string(&query).unwrap();
+        let parsed: Query = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, query);
+    }
+
+    #[test_case(">=2.1,<3" => ">=2.1,<3.0")]
+    #[test_case(" > 2.0 " => ">2.0")]
+    #[test_case("<=3.0-rc.1" => "<=3.0-rc.1")]
+    #[test_case("=2.2" => ">=2.2,<=2.2")]
+    fn range_query(query: &str) -> String {
+        query.parse::<Query>().unwrap().as_config_value()
+    }
+
+    #[test_case(">=2.1,<3", "2.1+a1b2c3d" => true)]
+    #[test_case(">=2.1,<3", "2.7+a1b2c3d" => true)]
+    #[test_case(">=2.1,<3", "2.0+a1b2c3d" => false)]
+    #[test_case(">=2.1,<3", "3.0+a1b2c3d" => false)]
+    #[test_case(">=2.1,<3", "3.0-rc.1+a1b2c3d" => false)]
+    #[test_case(">=3.0-beta.1", "3.0-rc.1+a1b2c3d" => true)]
+    #[test_case(">2", "3.0-dev.7000+a1b2c3d" => false)]
+    fn range_matches(query: &str, version: &str) -> bool {
+        query.parse::<Query>().unwrap().matches(&version.parse().unwrap())
+    }
+
+    #[test_case("2.3+abc12", "2.3+abc1234" => true)]
+    #[test_case("2.3+ABC1234", "2.3+abc1234" => true)]
+    #[test_case("2.3+abc1234", "2.3+abc1235" => false)]
+    #[test_case("2+abc1234", "2.4+abc1234" => true)]
+    #[test_case("nightly+abc", "3.0-dev.7000+abcdef0" => true)]
+    #[test_case("nightly+abd", "3.0-dev.7000+abcdef0" => false)]
+    fn build_hash_matches(query: &str, version: &str) -> bool {
+        query.parse::<Query>().unwrap().matches(&version.parse().unwrap())
+    }
+
+    #[test_case("2.3+abc1234" => "2.3+abc1234")]
+    #[test_case("nightly+abc1234" => "nightly+abc1234")]
+    #[test_case(">=2.1+abc1234" => ">=2.1+abc1234")]
+    fn build_hash_config_value(query: &str) -> String {
+        query.parse::<Query>().unwrap().as_config_value()
+    }
+
+    #[test_case("2.x")]
+    #[test_case("latest")]
+    #[test_case("2.3+" ; "empty hash")]
+    #[test_case("2.3+abc-123" ; "invalid hash")]
+    #[test_case(">=3,<2" ; "empty range")]
+    #[test_case(">=2,>=2.1" ; "duplicate bound")]
+    #[test_case("~2.1" ; "unknown operator")]
+    fn bad_query(query: &str) {
+        assert!(query.parse::<Query>().is_err());
+    }
+
+    #[test_case(None, Some(128), Some(64) => Some("blake2b".into()))]
+    #[test_case(None, None, Some(64) => Some("sha256".into()))]
+    #[test_case(None, Some(127), Some(64) => Some("sha256".into()))]
+    #[test_case(None, None, Some(128) => None)]
+    #[test_case(None, None, None => None)]
+    #[test_case(Some(64), Some(128), Some(64) => Some("blake3".into()))]
+    #[test_case(Some(128), Some(128), None => Some("blake2b".into()))]
+    fn verification_hash(blake3: Option<usize>, blake2b: Option<usize>,
+                         sha256: Option<usize>)
+        -> Option<String>
+    {
+        let verification = Verification {
+            size: 0,
+            blake3: blake3.map(|len| "c".repeat(len)),
+            blake2b: blake2b.map(|len| "a".repeat(len)),
+            sha256: sha256.map(|len| "b".repeat(len)),
+        };
+        verification.hash()
+            .map(|h| h.to_string().split(':').next().unwrap().into())
+    }
+
+    #[test]
+    fn verification_hash_preference() {
+        let verification = Verification {
+            size: 0,
+            blake3: Some("c".repeat(64)),
+            blake2b: Some("a".repeat(128)),
+            sha256: Some("b".repeat(64)),
+        };
+        let preference = [HashKind::Sha256, HashKind::Blake3];
+        let hash = verification.hash_with(&preference);
+        assert!(matches!(hash, Some(PackageHash::Sha256(_))));
+        assert!(verification.hash_with(&[]).is_none());
+    }
+
+    #[test_case("blake3,blake2b,sha256"
+                => vec![HashKind::Blake3, HashKind::Blake2b, HashKind::Sha256])]
+    #[test_case(" sha256 , blake2b,"
+                => vec![HashKind::Sha256, HashKind::Blake2b])]
+    fn hash_preference(val: &str) -> Vec<HashKind> {
+        parse_hash_preference(val).unwrap()
+    }
+
+    #[test_case("" ; "empty")]
+    #[test_case("sha256,md5" ; "unknown algorithm")]
+    fn bad_hash_preference(val: &str) {
+        assert!(parse_hash_preference(val).is_err());
+    }
+
+    fn install_ref(kind: &str, encoding: &str) -> InstallRef {
+        InstallRef {
+            path: format!("/archive/pkg{}", kind.len()),
+            kind: kind.into(),
+            encoding: Some(encoding.into()),
+            verification: Verification {
+                size: 1,
+                blake3: None,
+                blake2b: Some("a".repeat(128)),
+                sha256: None,
+            },
+        }
+    }
+
+    #[test_case(&[("application/x-tar", "zstd")] => Some(".tar.zst"))]
+    #[test_case(&[("application/zip", "identity")] => Some(".zip"))]
+    #[test_case(&[("application/zip", "identity"),
+                  ("application/x-tar", "zstd")] => Some(".tar.zst"))]
+    #[test_case(&[("application/x-tar", "identity")] => None)]
+    fn package_type(refs: &[(&str, &str)]) -> Option<&'static str> {
+        let pkg = PackageData {
+            basename: "edgedb-server".into(),
+            version: "2.3+abcdef0".into(),
+            installrefs: refs.iter()
+                .map(|(kind, enc)| install_ref(kind, enc))
+                .collect(),
+        };
+        let root = "https://packages.example.com/".parse().unwrap();
+        _filter_package(&root, &pkg).ok().map(|p| match p.kind {
+            PackageType::TarZst => ".tar.zst",
+            PackageType::Zip => ".zip",
+        })
+    }
+
+    #[test_case("https://example.com/archive/edgedb-server-2.3.tar.zst"
+                => Some(".tar.zst"))]
+    #[test_case("https://example.com/archive/edgedb-server-2.3.zip?x=1"
+                => Some(".zip"))]
+    #[test_case("https://example.com/archive/edgedb-server-2.3.tar.gz"
+                => None)]
+    fn package_type_from_url(url: &str) -> Option<&'static str> {
+        PackageType::from_url(&url.parse().unwrap()).map(|kind| match kind {
+            PackageType::TarZst => ".tar.zst",
+            PackageType::Zip => ".zip",
+        })
+    }
+
+    #[test_case("2.3+abcdef0", &[], "no install refs")]
+    #[test_case("latest", &[("application/x-tar", "zstd")],
+                "unparsable version")]
+    #[test_case("2.3+abcdef0", &[("application/x-tar", "gzip")],
+                "no usable install ref (/archive/pkg17: \
+                 unsupported encoding \"gzip\")")]
+    #[test_case("2.3+abcdef0", &[("application/x-rpm", "identity")],
+                "no usable install ref (/archive/pkg17: \
+                 unsupported type \"application/x-rpm\")")]
+    fn skip_reason(version: &str, refs: &[(&str, &str)], expected: &str) {
+        let pkg = PackageData {
+            basename: "edgedb-server".into(),
+            version: version.into(),
+            installrefs: refs.iter()
+                .map(|(kind, enc)| install_ref(kind, enc))
+                .collect(),
+        };
+        let root = "https://packages.example.com/".parse().unwrap();
+        let reason = _filter_package(&root, &pkg).unwrap_err();
+        assert!(reason.starts_with(expected), "{}", reason);
+    }
+
+    #[test_case("blake2b:0123456789abcdef" => "0123456")]
+    #[test_case("sha256:fedcba9876543210" => "fedcba9")]
+    #[test_case("md5:abc" => "abc")]
+    #[test_case("0123456789abcdef" => "9abcdef")]
+    #[test_case("abc" => "abc")]
+    #[test_case("" => "")]
+    fn unknown_hash_short(value: &str) -> String {
+        PackageHash::Unknown(value.into()).short().into()
+    }
+
+    #[test]
+    fn known_hash_short() {
+        let hash = PackageHash::Blake2b("0123456789abcdef".into());
+        assert_eq!(hash.short(), "0123456");
+        let hash = PackageHash::Sha256("fedcba9876543210".into());
+        assert_eq!(hash.short(), "fedcba9");
+    }
+
+    // 1994-11-06T08:49:37Z
+    const NOW: u64 = 784111777;
+
+    #[test_case("120" => Some(120))]
+    #[test_case(" 0 " => Some(0))]
+    #[test_case("100000" => Some(300))]
+    #[test_case("Sun, 06 Nov 1994 08:50:07 GMT" => Some(30))]
+    #[test_case("Sun, 06 Nov 1994 08:00:00 GMT" => Some(0))]
+    #[test_case("Mon, 07 Nov 1994 08:49:37 GMT" => Some(300))]
+    #[test_case("-5" => None)]
+    #[test_case("soon" => None)]
+    fn retry_after(value: &str) -> Option<u64> {
+        let now = UNIX_EPOCH + Duration::from_secs(NOW);
+        parse_retry_after(value, now).map(|d| d.as_secs())
+    }
+
+    #[test_case(NOW => true)]
+    #[test_case(NOW - 59 => true)]
+    #[test_case(NOW - 60 => false)]
+    #[test_case(NOW + 10 => false)]
+    fn index_cache_fresh(fetched_at: u64) -> bool {
+        let meta = IndexCacheMeta {
+            url: "https://packages.edgedb.com/".into(),
+            etag: None,
+            last_modified: None,
+            fetched_at,
+        };
+        is_fresh(&meta, UNIX_EPOCH + Duration::from_secs(NOW))
+    }
+
+    #[test_case("https://a.example" => 1)]
+    #[test_case("https://a.example, https://b.example/mirror/" => 2)]
+    #[test_case("https://a.example,,https://b.example," => 2)]
+    fn roots(value: &str) -> usize {
+        parse_roots(value).unwrap().len()
+    }
+
+    #[test_case("" ; "empty")]
+    #[test_case(" , " ; "only separators")]
+    #[test_case("https://a.example,not a url" ; "invalid url")]
+    fn bad_roots(value: &str) {
+        assert!(parse_roots(value).is_err());
+    }
+
+    fn blake2b(data: &[u8]) -> PackageHash {
+        PackageHash::Blake2b(
+            blake2b_simd::blake2b(data).to_hex().to_string().into())
+    }
+
+    #[test_case(b"data" => true)]
+    #[test_case(b"other" => false)]
+    #[test_case(b"" => false)]
+    fn verify(data: &[u8]) -> bool {
+        let digest = blake2b_simd::blake2b(data);
+        blake2b(b"data").verify_digest(digest.as_bytes())
+    }
+
+    #[test]
+    fn parse_progress() {
+        let data = format!("[{}]", vec!["1"; 100_000].join(","));
+        let mut last = (0, 0);
+        let value: Vec<u32> = parse_json(data.as_bytes(),
+            &mut |consumed, total| last = (consumed, total),
+            &AtomicBool::new(false)).unwrap();
+        assert_eq!(value.len(), 100_000);
+        assert_eq!(last, (data.len() as u64, data.len() as u64));
+    }
+
+    #[test]
+    fn parse_cancel() {
+        let data = format!("[{}]", vec!["1"; 100_000].join(","));
+        let cancel = AtomicBool::new(false);
+        let err = parse_json::<Vec<u32>>(data.as_bytes(),
+            &mut |_, _| cancel.store(true, Ordering::SeqCst),
+            &cancel).unwrap_err();
+        assert!(err.is::<Cancelled>());
+    }
+
+    #[test]
+    fn verify_digest() {
+        let hash = blake2b_simd::blake2b(b"data");
+        assert!(blake2b(b"data").verify_digest(hash.as_bytes()));
+        assert!(!blake2b(b"data").verify_digest(&hash.as_bytes()[..32]));
+        assert!(!PackageHash::Sha256("zz".into()).verify_digest(&[]));
+        assert!(!PackageHash::Sha256("00".into())
+                .verify_digest(hash.as_bytes()));
+        assert!(PackageHash::Unknown("md5:00".into())
+                .verify_digest(hash.as_bytes()));
+    }
+
+    #[test]
+    fn eta_smoothing() {
+        let start = Instant::now();
+        let mut eta = EtaEstimator::new();
+        eta.record(start, 0);
+        assert_eq!(eta.eta(1000), None);
+        // bursty transfer: 1000 bytes per second on average
+        for sec in 1..=4 {
+            let at = start + Duration::from_secs(sec);
+            eta.record(at, sec * 1000 - 900);
+            eta.record(at + Duration::from_millis(10), sec * 1000);
+        }
+        assert_eq!(eta.eta(8000).map(|d| d.as_secs()), Some(8));
+    }
+
+    #[test]
+    fn eta_window() {
+        let start = Instant::now();
+        let mut eta = EtaEstimator::new();
+        eta.record(start, 0);
+        eta.record(start + Duration::from_secs(1), 100_000);
+        // old fast samples are dropped after the window passes
+        for sec in 2..=20 {
+            eta.record(start + Duration::from_secs(sec), 100_000 + sec * 10);
+        }
+        assert_eq!(eta.rate().map(|r| r.round()), Some(10.0));
+    }
+
+    #[test]
+    fn eta_stalled() {
+        let start = Instant::now();
+        let mut eta = EtaEstimator::new();
+        eta.record(start, 500);
+        eta.record(start + Duration::from_secs(2), 500);
+        assert_eq!(eta.eta(1000), None);
     }
 }
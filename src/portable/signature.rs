@@ -0,0 +1,96 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::Context;
+use async_std::task;
+use fn_error_context::context;
+
+use crate::platform::tmp_file_path;
+use crate::portable::repository::{download, PackageInfo};
+use crate::process;
+
+
+/// ASCII-armored public key release packages are signed with
+const BUNDLED_RELEASE_KEY: &str = include_str!("release_key.asc");
+static REQUIRED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, thiserror::Error)]
+#[error("package {0} has no signature, cannot verify it")]
+pub struct SignatureMissing(String);
+
+#[derive(Debug, thiserror::Error)]
+#[error("signature of {0} is invalid")]
+pub struct BadSignature(String);
+
+/// Requires packages to be signed by the release key, set by
+/// `--verify-signature`
+pub fn set_required(required: bool) {
+    REQUIRED.store(required, Ordering::SeqCst);
+}
+
+pub fn is_required() -> bool {
+    REQUIRED.load(Ordering::SeqCst)
+}
+
+/// Returns the public key packages are signed with
+///
+/// The key is read from the ASCII-armored file `EDGEDB_RELEASE_KEY` points
+/// to. Release builds may bundle it into the binary instead, but the
+/// placeholder in the source tree isn't a key.
+fn release_key() -> anyhow::Result<Vec<u8>> {
+    if let Some(path) = env::var_os("EDGEDB_RELEASE_KEY")
+        .filter(|val| !val.is_empty())
+    {
+        let path = PathBuf::from(path);
+        return fs::read(&path)
+            .with_context(|| format!("cannot read release key {:?}", path));
+    }
+    if !BUNDLED_RELEASE_KEY.contains("-----BEGIN PGP PUBLIC KEY BLOCK-----") {
+        anyhow::bail!("this build has no bundled release key, \
+                       set EDGEDB_RELEASE_KEY to the path of the EdgeDB \
+                       release public key to verify signatures");
+    }
+    Ok(BUNDLED_RELEASE_KEY.as_bytes().to_vec())
+}
+
+/// Downloads detached signature of the package and checks `path` against it
+#[context("failed to verify signature of {}", pkg_info)]
+pub fn verify_package(pkg_info: &PackageInfo, path: &Path)
+    -> anyhow::Result<()>
+{
+    let sig_url = pkg_info.signature.as_ref()
+        .ok_or_else(|| SignatureMissing(pkg_info.to_string()))?;
+    let key = release_key()?;
+    let sig_path = tmp_file_path(&path.with_extension("sig"));
+    task::block_on(download(&sig_path, sig_url, true, false))?;
+    let result = verify_file(&key, path, &sig_path);
+    fs::remove_file(&sig_path)
+        .map_err(|e| {
+            log::warn!("Failed to remove {:?}: {}", sig_path, e);
+        }).ok();
+    result.map_err(|e| e.context(BadSignature(pkg_info.to_string())))
+}
+
+/// Runs `gpg` with a temporary home directory holding only the `key`
+fn verify_file(key: &[u8], path: &Path, sig_path: &Path)
+    -> anyhow::Result<()>
+{
+    let home = tempfile::tempdir()
+        .context("cannot create temporary directory for gpg")?;
+    let key_path = home.path().join("release_key.asc");
+    fs::write(&key_path, key)
+        .with_context(|| format!("cannot write {:?}", key_path))?;
+    process::Native::new("gpg key import", "gpg", "gpg")
+        .arg("--batch")
+        .arg("--homedir").arg(home.path())
+        .arg("--import").arg(&key_path)
+        .run()?;
+    process::Native::new("gpg signature check", "gpg", "gpg")
+        .arg("--batch")
+        .arg("--homedir").arg(home.path())
+        .arg("--verify").arg(sig_path).arg(path)
+        .run()?;
+    Ok(())
+}
@@ -526,6 +526,9 @@ impl FullStatus {
                 if let Ok(version) = inst.get_version() {
                     println!("  Version: {}", version);
                 }
+                if let Some(query) = &inst.query {
+                    println!("  Version query: {}", query.as_config_value());
+                }
                 if let Some(port) = self.reserved_port {
                     if inst.port == port {
                         println!("  Port: {}", port);
@@ -679,7 +682,7 @@ impl ConnectionStatus {
     }
 }
 
-fn status_str(status: &Service) -> &'static str {
+pub fn status_str(status: &Service) -> &'static str {
     match status {
         Service::Ready => "ready",
         Service::Running {..} => "running",
@@ -42,7 +42,7 @@ pub fn print_project_upgrade_command(version: &Query,
     eprintln!(
         "  edgedb project upgrade {}{}",
         match version.channel {
-            Channel::Stable => if let Some(filt) = &version.version {
+            Channel::Stable | Channel::Testing => if let Some(filt) = &version.version {
                 format!("--to-version={}", filt)
             } else {
                 "--to-latest".into()
@@ -88,15 +88,29 @@ fn check_project(name: &str, force: bool, ver_query: &Query)
 
 pub fn upgrade(options: &Upgrade) -> anyhow::Result<()> {
     let name = instance_arg(&options.name, &options.instance)?;
-    let inst = InstanceInfo::read(name)?;
+    if cfg!(windows) && options.dry_run {
+        // instance and its package index live inside WSL
+        return windows::upgrade(options);
+    }
+    let mut inst = InstanceInfo::read(name)?;
     let inst_ver = inst.get_version()?.specific();
     let ver_option = options.to_latest || options.to_nightly ||
         options.to_version.is_some();
     let ver_query = if ver_option {
-        Query::from_options(options.to_nightly, &options.to_version)?
+        let query = Query::from_options(options.to_nightly,
+                                        &options.to_version)?;
+        inst.query = Some(query.clone());
+        query
+    } else if let Some(query) = &inst.query {
+        query.clone()
     } else {
         Query::from_version(&inst_ver)?
     };
+    if options.dry_run {
+        let pkg = repository::get_server_package(&ver_query)?
+            .context("no package found according to your criteria")?;
+        return install::dry_run(&ver_query, &pkg, options.json);
+    }
     check_project(name, options.force, &ver_query)?;
 
     if cfg!(windows) {
@@ -36,6 +36,22 @@ pub struct Filter {
     pub minor: Option<FilterMinor>,
 }
 
+/// Version range such as `>=2.1,<3`
+///
+/// Prereleases only match if the lower bound is a prerelease itself,
+/// nightly versions never match.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Range {
+    pub lower: Option<Bound>,
+    pub upper: Option<Bound>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bound {
+    pub version: Specific,
+    pub inclusive: bool,
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum FilterMinor {
     Alpha(u32),
@@ -128,6 +144,121 @@ impl FromStr for Filter {
     }
 }
 
+impl From<&Filter> for Specific {
+    fn from(filter: &Filter) -> Specific {
+        let minor = match filter.minor {
+            None => MinorVersion::Minor(0),
+            Some(FilterMinor::Minor(v)) => MinorVersion::Minor(v),
+            Some(FilterMinor::Alpha(v)) => MinorVersion::Alpha(v),
+            Some(FilterMinor::Beta(v)) => MinorVersion::Beta(v),
+            Some(FilterMinor::Rc(v)) => MinorVersion::Rc(v),
+        };
+        Specific { major: filter.major, minor }
+    }
+}
+
+impl Range {
+    /// Returns true if the value looks like a range rather than a filter
+    pub fn is_range(value: &str) -> bool {
+        value.contains(|c| matches!(c, '<' | '>' | '='))
+    }
+    pub fn matches(&self, bld: &Build) -> bool {
+        use MinorVersion as M;
+
+        let spec = bld.specific();
+        match spec.minor {
+            M::Dev(_) => return false,
+            M::Minor(_) => {}
+            M::Alpha(_) | M::Beta(_) | M::Rc(_) => {
+                let lower_pre = self.lower.as_ref()
+                    .map(|b| !matches!(b.version.minor, M::Minor(_)))
+                    .unwrap_or(false);
+                if !lower_pre {
+                    return false;
+                }
+            }
+        }
+        let above = self.lower.as_ref().map(|b| {
+            if b.inclusive { spec >= b.version } else { spec > b.version }
+        }).unwrap_or(true);
+        let below = self.upper.as_ref().map(|b| {
+            if b.inclusive { spec <= b.version } else { spec < b.version }
+        }).unwrap_or(true);
+        above && below
+    }
+}
+
+impl FromStr for Range {
+    type Err = anyhow::Error;
+    fn from_str(value: &str) -> anyhow::Result<Range> {
+        let mut range = Range { lower: None, upper: None };
+        for item in value.split(',').map(|x| x.trim()) {
+            let (op, ver) = ["<=", ">=", "<", ">", "="].iter()
+                .find_map(|op| item.strip_prefix(op).map(|v| (*op, v)))
+                .with_context(|| format!("invalid range item {:?}, \
+                    expected one of `>=`, `>`, `<=`, `<`, `=` \
+                    followed by a version", item))?;
+            let version = Specific::from(&ver.trim().parse::<Filter>()?);
+            let (slot, inclusive) = match op {
+                ">=" => (&mut range.lower, true),
+                ">" => (&mut range.lower, false),
+                "<=" => (&mut range.upper, true),
+                "<" => (&mut range.upper, false),
+                _ => {
+                    if range.upper.is_some() {
+                        anyhow::bail!("duplicate bound in version range {:?}",
+                                      value);
+                    }
+                    range.upper = Some(Bound {
+                        version: version.clone(),
+                        inclusive: true,
+                    });
+                    (&mut range.lower, true)
+                }
+            };
+            if slot.is_some() {
+                anyhow::bail!("duplicate bound in version range {:?}", value);
+            }
+            *slot = Some(Bound { version, inclusive });
+        }
+        if let (Some(lower), Some(upper)) = (&range.lower, &range.upper) {
+            if lower.version > upper.version {
+                anyhow::bail!("version range {:?} is empty", value);
+            }
+        }
+        Ok(range)
+    }
+}
+
+impl fmt::Display for Range {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(lower) = &self.lower {
+            let op = if lower.inclusive { ">=" } else { ">" };
+            write!(f, "{}{}", op, lower.version)?;
+        }
+        if let Some(upper) = &self.upper {
+            if self.lower.is_some() {
+                f.write_str(",")?;
+            }
+            let op = if upper.inclusive { "<=" } else { "<" };
+            write!(f, "{}{}", op, upper.version)?;
+        }
+        Ok(())
+    }
+}
+
+impl IntoArg for &Build {
+    fn add_arg(self, process: &mut process::Native) {
+        process.arg(self.to_string());
+    }
+}
+
+impl IntoArg for &Specific {
+    fn add_arg(self, process: &mut process::Native) {
+        process.arg(self.to_string());
+    }
+}
+
 impl IntoArg for &Filter {
     fn add_arg(self, process: &mut process::Native) {
         process.arg(self.to_string());
@@ -155,6 +286,10 @@ impl Build {
     pub fn specific(&self) -> Specific {
         Specific::from_str(&self.0[..]).expect("build version is valid")
     }
+    /// Build hash, i.e. the part after `+`
+    pub fn hash(&self) -> Option<&str> {
+        self.0.split_once('+').map(|(_, hash)| hash)
+    }
     fn comparator(&self) -> Specific {
         self.specific()
     }
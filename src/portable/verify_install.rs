@@ -0,0 +1,233 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use fn_error_context::context;
+
+use crate::platform::{portable_dir, tmp_file_path};
+use crate::portable::cache;
+use crate::portable::hash::{hash_reader, HashKind};
+use crate::portable::install::{package_relative_path, unpack_package};
+use crate::portable::install::fetch_package;
+use crate::portable::local::{InstallInfo, write_json};
+use crate::portable::options::VerifyInstall;
+use crate::portable::repository::{PackageInfo, PackageType};
+use crate::print::{echo, Highlight};
+use crate::table::{self, Table, Row, Cell};
+
+
+#[derive(Debug)]
+enum Drift {
+    Missing(PathBuf),
+    Modified(PathBuf),
+    Extra(PathBuf),
+}
+
+pub fn verify_install(options: &VerifyInstall) -> anyhow::Result<()> {
+    let dir = portable_dir()?.join(options.version.to_string());
+    let info = InstallInfo::read(&dir)
+        .with_context(|| format!("version {} is not installed",
+                                 options.version))?;
+    let kind = PackageType::from_url(&info.package_url)
+        .with_context(|| format!("unknown package type of {}",
+                                 info.package_url))?;
+    let pkg_info = PackageInfo {
+        version: info.version.clone(),
+        url: info.package_url.clone(),
+        size: 0,
+        hash: info.package_hash.clone(),
+        kind,
+        signature: None,
+    };
+    let (cache_path, downloaded) = fetch_package(&pkg_info,
+                                                 &cache::download_dir()?)?;
+    let result = check(&cache_path, &pkg_info.kind, &dir).and_then(|drift| {
+        if drift.is_empty() {
+            echo!("Installation of", info.version.emphasize(),
+                  "matches the package.");
+            return Ok(());
+        }
+        print_drift(&dir, &drift);
+        if options.repair {
+            repair(&cache_path, &dir, &info)?;
+            echo!("Successfully repaired", info.version.emphasize());
+            Ok(())
+        } else {
+            anyhow::bail!("{} files differ from the package. \
+                           Run with `--repair` to re-extract it.",
+                           drift.len());
+        }
+    });
+    if downloaded {
+        fs::remove_file(&cache_path)
+            .map_err(|e| {
+                log::warn!("Failed to remove cache {:?}: {}", cache_path, e);
+            }).ok();
+    }
+    result
+}
+
+#[context("failed to compare {:?} with {:?}", archive, dir)]
+fn check(archive: &Path, kind: &PackageType, dir: &Path)
+    -> anyhow::Result<Vec<Drift>>
+{
+    let mut drift = Vec::new();
+    let mut expected = BTreeSet::new();
+    match kind {
+        PackageType::TarZst => {
+            check_tar(archive, dir, &mut drift, &mut expected)?;
+        }
+        PackageType::Zip => {
+            check_zip(archive, dir, &mut drift, &mut expected)?;
+        }
+    }
+    for relative in list_files(dir)? {
+        if relative != Path::new("install_info.json")
+            && !expected.contains(&relative)
+        {
+            drift.push(Drift::Extra(relative));
+        }
+    }
+    Ok(drift)
+}
+
+fn check_tar(archive: &Path, dir: &Path,
+             drift: &mut Vec<Drift>, expected: &mut BTreeSet<PathBuf>)
+    -> anyhow::Result<()>
+{
+    let file = fs::File::open(archive)?;
+    let file = zstd::Decoder::new(io::BufReader::new(file))?;
+    let mut arch = tar::Archive::new(file);
+
+    for entry in arch.entries()? {
+        let mut entry = entry?;
+        let relative = match package_relative_path(&*entry.path()?)? {
+            Some(relative) => relative,
+            None => continue,
+        };
+        let path = dir.join(&relative);
+        expected.insert(relative.clone());
+        let entry_type = entry.header().entry_type();
+        if entry_type.is_file() {
+            check_file(&path, &mut entry, relative, drift)?;
+        } else if entry_type.is_symlink() {
+            match fs::read_link(&path) {
+                Ok(target) => {
+                    if entry.link_name()?.as_deref() != Some(&*target) {
+                        drift.push(Drift::Modified(relative));
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                    drift.push(Drift::Missing(relative));
+                }
+                Err(_) => drift.push(Drift::Modified(relative)),
+            }
+        } else if entry_type.is_dir() && !path.is_dir() {
+            drift.push(Drift::Missing(relative));
+        }
+    }
+    Ok(())
+}
+
+fn check_zip(archive: &Path, dir: &Path,
+             drift: &mut Vec<Drift>, expected: &mut BTreeSet<PathBuf>)
+    -> anyhow::Result<()>
+{
+    let mut zip = zip::ZipArchive::new(
+        io::BufReader::new(fs::File::open(archive)?))?;
+    for index in 0..zip.len() {
+        let mut entry = zip.by_index(index)?;
+        let name = entry.enclosed_name()
+            .with_context(|| format!("invalid path {:?} in archive",
+                                     entry.name()))?
+            .to_path_buf();
+        let relative = match package_relative_path(&name)? {
+            Some(relative) => relative,
+            None => continue,
+        };
+        let path = dir.join(&relative);
+        expected.insert(relative.clone());
+        if entry.is_dir() {
+            if !path.is_dir() {
+                drift.push(Drift::Missing(relative));
+            }
+        } else {
+            check_file(&path, &mut entry, relative, drift)?;
+        }
+    }
+    Ok(())
+}
+
+fn check_file(path: &Path, entry: impl Read, relative: PathBuf,
+              drift: &mut Vec<Drift>)
+    -> anyhow::Result<()>
+{
+    match fs::File::open(path) {
+        Ok(file) => {
+            if hash_reader(file, HashKind::Blake2b, |_| {})?
+                != hash_reader(entry, HashKind::Blake2b, |_| {})?
+            {
+                drift.push(Drift::Modified(relative));
+            }
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            drift.push(Drift::Missing(relative));
+        }
+        Err(e) => return Err(e)?,
+    }
+    Ok(())
+}
+
+/// Lists all files and symlinks under `dir` relative to it
+fn list_files(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut result = Vec::new();
+    let mut queue = vec![PathBuf::new()];
+    while let Some(relative) = queue.pop() {
+        for item in fs::read_dir(dir.join(&relative))? {
+            let item = item?;
+            let path = relative.join(item.file_name());
+            if item.file_type()?.is_dir() {
+                queue.push(path);
+            } else {
+                result.push(path);
+            }
+        }
+    }
+    result.sort();
+    Ok(result)
+}
+
+fn print_drift(dir: &Path, drift: &[Drift]) {
+    let mut table = Table::new();
+    table.set_format(*table::FORMAT);
+    table.set_titles(Row::new(
+        ["Path", "Problem"]
+        .iter().map(|x| table::header_cell(x)).collect()));
+    for item in drift {
+        let (path, problem) = match item {
+            Drift::Missing(path) => (path, "missing"),
+            Drift::Modified(path) => (path, "modified"),
+            Drift::Extra(path) => (path, "not in package"),
+        };
+        table.add_row(Row::new(vec![
+            Cell::new(&dir.join(path).display().to_string()),
+            Cell::new(problem),
+        ]));
+    }
+    table.printstd();
+}
+
+#[context("failed to repair installation at {:?}", dir)]
+fn repair(archive: &Path, dir: &Path, info: &InstallInfo)
+    -> anyhow::Result<()>
+{
+    let tmp_target = tmp_file_path(dir);
+    unpack_package(archive, &tmp_target)?;
+    write_json(&tmp_target.join("install_info.json"), "metadata", info)?;
+    fs::remove_dir_all(dir)?;
+    fs::rename(&tmp_target, dir).with_context(
+        || format!("cannot rename {:?} -> {:?}", tmp_target, dir))?;
+    Ok(())
+}
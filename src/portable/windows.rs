@@ -205,16 +205,25 @@ pub fn destroy(options: &options::Destroy) -> anyhow::Result<()> {
     let paths = Paths::get(&name)?;
     if paths.credentials.exists() {
         found = true;
-        log::info!(target: "edgedb::portable::destroy",
-                   "Removing credentials file {:?}", &paths.credentials);
-        fs::remove_file(&paths.credentials)?;
+        if options.dry_run {
+            echo!("Would remove credentials file",
+                  paths.credentials.display());
+        } else {
+            log::info!(target: "edgedb::portable::destroy",
+                       "Removing credentials file {:?}", &paths.credentials);
+            fs::remove_file(&paths.credentials)?;
+        }
     }
     for path in &paths.service_files {
         if path.exists() {
             found = true;
-            log::info!(target: "edgedb::portable::destroy",
-                       "Removing service file {:?}", path);
-            fs::remove_file(path)?;
+            if options.dry_run {
+                echo!("Would remove service file", path.display());
+            } else {
+                log::info!(target: "edgedb::portable::destroy",
+                           "Removing service file {:?}", path);
+                fs::remove_file(path)?;
+            }
         }
     }
     if !found {
@@ -634,6 +643,16 @@ pub fn install(options: &options::Install) -> anyhow::Result<()> {
     Ok(())
 }
 
+pub fn install_from_url(options: &options::InstallFromUrl)
+    -> anyhow::Result<()>
+{
+    ensure_wsl()?
+        .edgedb()
+        .arg("server").arg("install-from-url").args(options)
+        .run()?;
+    Ok(())
+}
+
 pub fn uninstall(options: &options::Uninstall) -> anyhow::Result<()> {
     if let Some(wsl) = get_wsl()? {
         wsl.edgedb()
@@ -672,6 +691,76 @@ pub fn info(options: &options::Info) -> anyhow::Result<()> {
     Ok(())
 }
 
+pub fn list_cache(options: &options::ListCache) -> anyhow::Result<()> {
+    if let Some(wsl) = get_wsl()? {
+        wsl.edgedb()
+            .arg("server").arg("list-cache").args(options)
+            .run()?;
+    } else if options.json {
+        println!("[]");
+    } else {
+        print::warn("No cached packages found");
+    }
+    Ok(())
+}
+
+pub fn verify_cache(options: &options::VerifyCache) -> anyhow::Result<()> {
+    if let Some(wsl) = get_wsl()? {
+        wsl.edgedb()
+            .arg("server").arg("verify-cache").args(options)
+            .run()?;
+    } else {
+        print::warn("No cached packages found");
+    }
+    Ok(())
+}
+
+pub fn cache_path(options: &options::CachePath) -> anyhow::Result<()> {
+    try_get_wsl()?
+        .edgedb()
+        .arg("server").arg("cache-path").args(options)
+        .run()?;
+    Ok(())
+}
+
+pub fn check_package(options: &options::CheckPackage) -> anyhow::Result<()> {
+    let path = fs::canonicalize(&options.path)
+        .with_context(|| format!("cannot open {:?}", options.path))?;
+    let mut cmd = try_get_wsl()?.edgedb();
+    cmd.arg("server").arg("check-package").arg(path_to_linux(&path)?);
+    if options.nightly {
+        cmd.arg("--nightly");
+    }
+    if let Some(version) = &options.version {
+        cmd.arg("--version").arg(version.to_string());
+    }
+    if options.json {
+        cmd.arg("--json");
+    }
+    cmd.run()?;
+    Ok(())
+}
+
+pub fn verify_install(options: &options::VerifyInstall) -> anyhow::Result<()> {
+    if let Some(wsl) = get_wsl()? {
+        wsl.edgedb()
+            .arg("server").arg("verify-install").args(options)
+            .run()?;
+    } else {
+        anyhow::bail!("WSL distribution is not installed, \
+                       so no EdgeDB server versions are present.");
+    }
+    Ok(())
+}
+
+pub fn resolve_query(options: &options::ResolveQuery) -> anyhow::Result<()> {
+    ensure_wsl()?
+        .edgedb()
+        .arg("server").arg("resolve-query").args(options)
+        .run()?;
+    Ok(())
+}
+
 pub fn reset_password(options: &options::ResetPassword) -> anyhow::Result<()> {
     let name = instance_arg(&options.name, &options.instance)?;
     if let Some(wsl) = get_wsl()? {
@@ -848,6 +937,10 @@ pub fn upgrade(options: &options::Upgrade) -> anyhow::Result<()> {
         .arg("upgrade")
         .args(options)
         .run()?;
+    if options.dry_run {
+        // dry run changes nothing, so there are no credentials to copy
+        return Ok(());
+    }
     // credentials might be updated on upgrade if we change format somehow
     wsl.copy_out(credentials_linux(name), credentials::path(name)?)?;
     Ok(())
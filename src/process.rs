@@ -125,6 +125,12 @@ impl IntoArg for &String {
     }
 }
 
+impl IntoArg for &url::Url {
+    fn add_arg(self, process: &mut Native) {
+        process.arg(self.as_str());
+    }
+}
+
 impl IntoArg for &u16 {
     fn add_arg(self, process: &mut Native) {
         process.arg(self.to_string());
@@ -137,6 +143,12 @@ impl IntoArg for &usize {
     }
 }
 
+impl IntoArg for &u64 {
+    fn add_arg(self, process: &mut Native) {
+        process.arg(self.to_string());
+    }
+}
+
 pub trait IntoArgs {
     fn add_args(self, process: &mut Native);
 }